@@ -467,7 +467,7 @@ impl<'a> Frame<'a> {
 
 type BlendFn = Box<dyn Fn(Color8, Color8, u8) -> Color8>;
 
-fn blend_mode_to_blend_fn(mode: BlendMode) -> BlendFn {
+pub(crate) fn blend_mode_to_blend_fn(mode: BlendMode) -> BlendFn {
     // TODO: Make these statically allocated
     match mode {
         BlendMode::Normal => Box::new(blend::normal),
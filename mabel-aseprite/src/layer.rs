@@ -1,5 +1,7 @@
 use crate::{
+    blend::Color8,
     cel::{Cel, CelId},
+    file::blend_mode_to_blend_fn,
     reader::AseReader,
     tileset::TilesetsById,
     user_data::UserData,
@@ -222,6 +224,15 @@ pub enum BlendMode {
     Divide,
 }
 
+impl BlendMode {
+    /// Blend `src` onto `backdrop` using this blend mode, the same way
+    /// [Frame::image](crate::Frame::image) combines layers. `opacity` is the
+    /// layer opacity in the range `0..=255`.
+    pub fn blend(self, backdrop: Color8, src: Color8, opacity: u8) -> Color8 {
+        blend_mode_to_blend_fn(self)(backdrop, src, opacity)
+    }
+}
+
 pub(crate) fn parse_chunk(data: &[u8]) -> Result<LayerData> {
     let mut reader = AseReader::new(data);
 
@@ -185,6 +185,7 @@ mod tileset;
 pub(crate) mod user_data;
 #[cfg(feature = "utils")]
 pub mod util;
+pub mod writer;
 
 /// A specialized `Result` type for Aseprite parsing functions.
 pub type Result<T> = std::result::Result<T, AsepriteParseError>;
@@ -186,9 +186,11 @@ impl RawPixels {
 }
 
 impl Pixels {
-    // Returns a Borrowed Cow if the Pixels struct already contains Rgba pixels.
-    // Otherwise clones them to create an Owned Cow.
-    pub(crate) fn clone_as_image_rgba(&self) -> Cow<Vec<image::Rgba<u8>>> {
+    /// The pixels as RGBA, resolving indexed and grayscale pixels.
+    ///
+    /// Returns a Borrowed Cow if the Pixels struct already contains Rgba pixels.
+    /// Otherwise clones them to create an Owned Cow.
+    pub fn clone_as_image_rgba(&self) -> Cow<'_, Vec<image::Rgba<u8>>> {
         match self {
             Pixels::Rgba(rgba) => Cow::Borrowed(rgba),
             Pixels::Grayscale(grayscale) => {
@@ -602,6 +602,305 @@ fn compute_indexed() {
     assert_eq!(data[7], 13);
 }
 
+fn write_and_read(writer: &writer::AsepriteWriter) -> AsepriteFile {
+    let mut data = Vec::new();
+    writer.write(&mut data).unwrap();
+    AsepriteFile::read(&data[..]).unwrap()
+}
+
+fn test_image(width: u32, height: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(width, height, |x, y| match (x + y) % 3 {
+        0 => image::Rgba(COLOR_RED),
+        1 => image::Rgba(COLOR_GREEN),
+        _ => image::Rgba([0, 0, 0, 0]),
+    })
+}
+
+#[test]
+fn writer_rgba() {
+    use writer::*;
+
+    let image = test_image(5, 3);
+    let mut ase = AsepriteWriter::new(5, 3);
+    ase.layers.push(NewLayer::new("Layer 1"));
+    let mut frame = NewFrame::new(120);
+    frame.cels.push(NewCel::image(0, image.clone()));
+    ase.frames.push(frame);
+
+    let f = write_and_read(&ase);
+    assert_eq!(f.size(), (5, 3));
+    assert_eq!(f.pixel_format(), PixelFormat::Rgba);
+    assert_eq!(f.num_layers(), 1);
+    assert_eq!(f.layer(0).name(), "Layer 1");
+    assert!(f.layer(0).is_visible());
+    assert_eq!(f.frame(0).duration(), 120);
+    assert_eq!(f.frame(0).image(), image);
+}
+
+#[test]
+fn writer_indexed() {
+    use writer::*;
+
+    let image = test_image(4, 4);
+    let mut ase = AsepriteWriter::new(4, 4);
+    ase.pixel_format = PixelFormat::Indexed {
+        transparent_color_index: 2,
+    };
+    ase.palette = vec![
+        NewColor {
+            rgba: COLOR_RED,
+            name: Some("r".to_owned()),
+        },
+        COLOR_GREEN.into(),
+        [0, 0, 0, 0].into(),
+    ];
+    ase.layers.push(NewLayer::new("Layer 1"));
+    let mut frame = NewFrame::new(100);
+    frame.cels.push(NewCel::image(0, image.clone()));
+    ase.frames.push(frame);
+
+    let f = write_and_read(&ase);
+    assert_eq!(
+        f.pixel_format(),
+        PixelFormat::Indexed {
+            transparent_color_index: 2
+        }
+    );
+    let palette = f.palette().unwrap();
+    assert_eq!(palette.num_colors(), 3);
+    assert_eq!(palette.color(0).unwrap().raw_rgba8(), COLOR_RED);
+    assert_eq!(palette.color(0).unwrap().name(), Some("r"));
+    assert_eq!(palette.color(1).unwrap().raw_rgba8(), COLOR_GREEN);
+    assert_eq!(palette.color(1).unwrap().name(), None);
+    assert_eq!(f.frame(0).image(), image);
+}
+
+#[test]
+fn writer_layers_and_frames() {
+    use writer::*;
+
+    let image = test_image(3, 3);
+    let mut ase = AsepriteWriter::new(8, 8);
+    let mut group = NewLayer::new("Group");
+    group.is_group = true;
+    ase.layers.push(group);
+    let mut layer = NewLayer::new("Inner");
+    layer.child_level = 1;
+    layer.blend_mode = BlendMode::Multiply;
+    layer.opacity = 128;
+    ase.layers.push(layer);
+    let mut hidden = NewLayer::new("Hidden");
+    hidden.flags = LayerFlags::EDITABLE;
+    ase.layers.push(hidden);
+
+    let mut first = NewFrame::new(50);
+    let mut cel = NewCel::image(1, image.clone());
+    (cel.x, cel.y) = (2, 4);
+    first.cels.push(cel);
+    ase.frames.push(first);
+    let mut second = NewFrame::new(75);
+    second.cels.push(NewCel::linked(1, 0));
+    ase.frames.push(second);
+    let mut tag = NewTag::new("walk", 0, 1);
+    tag.animation_direction = AnimationDirection::PingPong;
+    tag.repeat = 3;
+    ase.tags.push(tag);
+
+    let f = write_and_read(&ase);
+    assert_eq!(f.num_frames(), 2);
+    assert_eq!(f.num_layers(), 3);
+    assert_eq!(f.layer(0).layer_type(), LayerType::Group);
+    assert_eq!(f.layer(1).parent().unwrap().id(), 0);
+    assert_eq!(f.layer(1).blend_mode(), BlendMode::Multiply);
+    assert_eq!(f.layer(1).opacity(), 128);
+    assert!(!f.layer(2).is_visible());
+    assert_eq!(f.frame(1).duration(), 75);
+    let tag = f.tag_by_name("walk").unwrap();
+    assert_eq!((tag.from_frame(), tag.to_frame()), (0, 1));
+    assert_eq!(tag.animation_direction(), AnimationDirection::PingPong);
+    assert_eq!(tag.repeat().unwrap().get(), 3);
+    assert_eq!(f.cel(0, 1).top_left(), (2, 4));
+    assert_eq!(f.cel(1, 1).image(), f.cel(0, 1).image());
+    // cel images include the layer opacity
+    assert_eq!(
+        *f.cel(0, 1).image().get_pixel(2, 4),
+        image::Rgba([255, 0, 0, 128])
+    );
+}
+
+#[test]
+fn writer_grayscale() {
+    use writer::*;
+
+    let image = image::RgbaImage::from_fn(4, 2, |x, y| match (x, y) {
+        (3, _) => image::Rgba([0, 0, 0, 0]),
+        _ => image::Rgba([
+            x as u8 * 80,
+            x as u8 * 80,
+            x as u8 * 80,
+            255 - y as u8 * 100,
+        ]),
+    });
+    let mut ase = AsepriteWriter::new(4, 2);
+    ase.pixel_format = PixelFormat::Grayscale;
+    ase.layers.push(NewLayer::new("Layer 1"));
+    let mut frame = NewFrame::new(100);
+    frame.cels.push(NewCel::image(0, image.clone()));
+    ase.frames.push(frame);
+
+    let f = write_and_read(&ase);
+    assert_eq!(f.pixel_format(), PixelFormat::Grayscale);
+    assert_eq!(f.frame(0).image(), image);
+
+    ase.frames[0].cels[0] = NewCel::image(0, test_image(4, 2));
+    // red isn't gray
+    assert!(ase.write(Vec::new()).is_err());
+}
+
+#[test]
+fn writer_tilemap() {
+    use writer::*;
+
+    let mut ase = AsepriteWriter::new(6, 4);
+    let mut tileset = NewTileset::new("Tiles", 2, 2);
+    tileset.tiles = vec![
+        image::RgbaImage::from_pixel(2, 2, image::Rgba(COLOR_RED)),
+        test_image(2, 2),
+    ];
+    ase.tilesets.push(tileset);
+    let mut layer = NewLayer::new("Tilemap");
+    layer.tileset = Some(0);
+    layer.opacity = 128;
+    ase.layers.push(layer);
+    let mut frame = NewFrame::new(100);
+    // two rows of three tiles, the last one empty
+    frame
+        .cels
+        .push(NewCel::tilemap(0, 3, vec![1, 2, 1, 2, 1, 0]));
+    ase.frames.push(frame);
+
+    let f = write_and_read(&ase);
+    assert_eq!(f.layer(0).layer_type(), LayerType::Tilemap(0));
+    let tileset = f.tilesets().get(0).unwrap();
+    assert_eq!(tileset.name(), "Tiles");
+    assert_eq!(tileset.tile_count(), 3);
+    let tilemap = f.tilemap(0, 0).unwrap();
+    assert_eq!((tilemap.width(), tilemap.height()), (3, 2));
+    assert_eq!(tilemap.tile(1, 0).id(), 2);
+    assert_eq!(tilemap.tile(2, 1).id(), 0);
+
+    let image = f.frame(0).image();
+    // tiles are drawn with the layer opacity
+    assert_eq!(*image.get_pixel(0, 0), image::Rgba([255, 0, 0, 128]));
+    assert_eq!(*image.get_pixel(3, 0), image::Rgba([0, 255, 0, 128]));
+    assert_eq!(image.get_pixel(5, 3)[3], 0);
+
+    // tile 3 is missing
+    ase.frames[0].cels[0] = NewCel::tilemap(0, 3, vec![3, 0, 0]);
+    assert!(ase.write(Vec::new()).is_err());
+    // a single tile doesn't fill a row
+    ase.frames[0].cels[0] = NewCel::tilemap(0, 3, vec![1]);
+    assert!(ase.write(Vec::new()).is_err());
+    // tilemap layers only hold tilemaps
+    ase.frames[0].cels[0] = NewCel::image(0, test_image(2, 2));
+    assert!(ase.write(Vec::new()).is_err());
+}
+
+#[test]
+fn writer_invalid() {
+    use writer::*;
+
+    let mut ase = AsepriteWriter::new(2, 2);
+    assert!(ase.write(Vec::new()).is_err());
+
+    let mut frame = NewFrame::new(100);
+    frame.cels.push(NewCel::image(0, test_image(2, 2)));
+    ase.frames.push(frame);
+    // the cel's layer is missing
+    assert!(ase.write(Vec::new()).is_err());
+
+    ase.layers.push(NewLayer::new("Layer 1"));
+    ase.pixel_format = PixelFormat::Indexed {
+        transparent_color_index: 0,
+    };
+    ase.palette = vec![[0, 0, 0, 0].into(), COLOR_RED.into()];
+    // green isn't in the palette
+    assert!(ase.write(Vec::new()).is_err());
+
+    ase.palette.push(COLOR_GREEN.into());
+    assert!(ase.write(Vec::new()).is_ok());
+}
+
+#[test]
+fn writer_slices_and_user_data() {
+    use writer::*;
+
+    let user_data = |text: &str| UserData {
+        text: Some(text.to_owned()),
+        color: Some(image::Rgba([1, 2, 3, 4])),
+    };
+    let mut ase = AsepriteWriter::new(8, 8);
+    ase.user_data = Some(user_data("sprite"));
+    let mut layer = NewLayer::new("Layer 1");
+    layer.user_data = Some(user_data("layer"));
+    ase.layers.push(layer);
+    let mut frame = NewFrame::new(100);
+    let mut cel = NewCel::image(0, test_image(2, 2));
+    cel.user_data = Some(user_data("cel"));
+    frame.cels.push(cel);
+    ase.frames.push(frame);
+    ase.tags.push(NewTag::new("idle", 0, 0));
+    let mut tag = NewTag::new("walk", 0, 0);
+    tag.user_data = Some(user_data("walk"));
+    ase.tags.push(tag);
+    ase.slices.push(Slice {
+        name: "button".to_owned(),
+        keys: vec![SliceKey {
+            from_frame: 0,
+            origin: (1, -2),
+            size: (5, 6),
+            slice9: Some(Slice9 {
+                center_x: 1,
+                center_y: 1,
+                center_width: 3,
+                center_height: 4,
+            }),
+            pivot: Some((2, 3)),
+        }],
+        user_data: Some(user_data("slice")),
+    });
+
+    let f = write_and_read(&ase);
+    let text = |u: Option<&UserData>| u.and_then(|u| u.text.clone());
+    assert_eq!(text(f.sprite_user_data()).as_deref(), Some("sprite"));
+    assert_eq!(
+        f.sprite_user_data().unwrap().color,
+        Some(image::Rgba([1, 2, 3, 4]))
+    );
+    assert_eq!(text(f.layer(0).user_data()).as_deref(), Some("layer"));
+    assert_eq!(text(f.cel(0, 0).user_data()).as_deref(), Some("cel"));
+    assert_eq!(text(f.tag(0).user_data()), None);
+    assert_eq!(text(f.tag(1).user_data()).as_deref(), Some("walk"));
+    let [slice] = f.slices() else {
+        panic!("expected a single slice");
+    };
+    assert_eq!(slice.name, "button");
+    assert_eq!(text(slice.user_data.as_ref()).as_deref(), Some("slice"));
+    let key = &slice.keys[0];
+    assert_eq!(
+        (key.origin, key.size, key.pivot),
+        ((1, -2), (5, 6), Some((2, 3)))
+    );
+    assert_eq!(key.slice9.as_ref().unwrap().center_height, 4);
+
+    // every key of a slice has the same kind of data
+    let mut key = ase.slices[0].keys[0].clone();
+    key.from_frame = 1;
+    key.pivot = None;
+    ase.slices[0].keys.push(key);
+    assert!(ase.write(Vec::new()).is_err());
+}
+
 /*
 #[test]
 fn gen_random_pixels() {
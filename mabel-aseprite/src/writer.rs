@@ -0,0 +1,789 @@
+use crate::{
+    AnimationDirection, AsepriteParseError, BlendMode, LayerFlags, PixelFormat, Result, Slice,
+    UserData,
+};
+use byteorder::{LittleEndian, WriteBytesExt};
+use flate2::{write::ZlibEncoder, Compression};
+use image::RgbaImage;
+use std::{collections::HashMap, fs::File, io::Write, path::Path};
+
+/// Builds an Aseprite file that [AsepriteFile](crate::AsepriteFile) can read
+/// back.
+///
+/// Cels are given as RGBA images. In indexed files every pixel is looked up in
+/// the palette, fully transparent pixels use the transparent color index. In
+/// grayscale files every pixel has the same red, green and blue.
+///
+/// ```
+/// # use mabel_aseprite::{writer::{AsepriteWriter, NewCel, NewFrame, NewLayer}, AsepriteFile};
+/// let mut writer = AsepriteWriter::new(16, 16);
+/// writer.layers.push(NewLayer::new("Layer 1"));
+/// let mut frame = NewFrame::new(100);
+/// frame.cels.push(NewCel::image(0, image::RgbaImage::new(16, 16)));
+/// writer.frames.push(frame);
+///
+/// let mut data = Vec::new();
+/// writer.write(&mut data).unwrap();
+/// let ase = AsepriteFile::read(&data[..]).unwrap();
+/// assert_eq!(ase.num_frames(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsepriteWriter {
+    /// Width in pixels.
+    pub width: u16,
+    /// Height in pixels.
+    pub height: u16,
+    pub pixel_format: PixelFormat,
+    /// The palette entries, in order.
+    pub palette: Vec<NewColor>,
+    /// The tilesets of tilemap layers, by index.
+    pub tilesets: Vec<NewTileset>,
+    /// The layers from bottom to top, groups before their children.
+    pub layers: Vec<NewLayer>,
+    /// The animation frames, a file needs at least one.
+    pub frames: Vec<NewFrame>,
+    pub tags: Vec<NewTag>,
+    pub slices: Vec<Slice>,
+    /// User data of the whole sprite.
+    pub user_data: Option<UserData>,
+}
+
+/// A palette entry with an optional name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewColor {
+    pub rgba: [u8; 4],
+    pub name: Option<String>,
+}
+
+impl From<[u8; 4]> for NewColor {
+    fn from(rgba: [u8; 4]) -> Self {
+        Self { rgba, name: None }
+    }
+}
+
+/// A set of tiles of the same size.
+#[derive(Debug, Clone)]
+pub struct NewTileset {
+    pub name: String,
+    pub tile_width: u16,
+    pub tile_height: u16,
+    /// The tiles from id 1, id 0 is the empty tile.
+    pub tiles: Vec<RgbaImage>,
+}
+
+impl NewTileset {
+    /// A tileset without tiles besides the empty one.
+    pub fn new(name: &str, tile_width: u16, tile_height: u16) -> Self {
+        Self {
+            name: name.to_owned(),
+            tile_width,
+            tile_height,
+            tiles: Vec::new(),
+        }
+    }
+}
+
+/// An image, group or tilemap layer.
+#[derive(Debug, Clone)]
+pub struct NewLayer {
+    pub name: String,
+    pub flags: LayerFlags,
+    /// Group layers hold no cels, only other layers.
+    pub is_group: bool,
+    /// The tileset index of a tilemap layer, whose cels are tilemaps.
+    pub tileset: Option<u32>,
+    /// How deeply the layer is nested in groups, 0 for top level layers.
+    pub child_level: u16,
+    pub blend_mode: BlendMode,
+    pub opacity: u8,
+    pub user_data: Option<UserData>,
+}
+
+impl NewLayer {
+    /// A visible, editable image layer with normal blending.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            flags: LayerFlags::VISIBLE | LayerFlags::EDITABLE,
+            is_group: false,
+            tileset: None,
+            child_level: 0,
+            blend_mode: BlendMode::Normal,
+            opacity: 255,
+            user_data: None,
+        }
+    }
+}
+
+/// A frame and its cels.
+#[derive(Debug, Clone)]
+pub struct NewFrame {
+    /// Frame duration in milliseconds.
+    pub duration: u16,
+    pub cels: Vec<NewCel>,
+}
+
+impl NewFrame {
+    pub fn new(duration: u16) -> Self {
+        Self {
+            duration,
+            cels: Vec::new(),
+        }
+    }
+}
+
+/// The cel of a layer in a frame.
+#[derive(Debug, Clone)]
+pub struct NewCel {
+    pub layer: u16,
+    pub x: i16,
+    pub y: i16,
+    pub opacity: u8,
+    pub content: NewCelContent,
+    pub user_data: Option<UserData>,
+}
+
+#[derive(Debug, Clone)]
+pub enum NewCelContent {
+    /// Pixels placed at the cel position.
+    Image(RgbaImage),
+    /// The same cel as the one of this layer in another frame.
+    Linked(u16),
+    /// Tile ids in rows of `width` tiles, placed at the cel position.
+    Tilemap { width: u16, tiles: Vec<u32> },
+}
+
+impl NewCel {
+    /// An opaque cel at the top left corner.
+    pub fn image(layer: u16, image: RgbaImage) -> Self {
+        Self {
+            layer,
+            x: 0,
+            y: 0,
+            opacity: 255,
+            content: NewCelContent::Image(image),
+            user_data: None,
+        }
+    }
+
+    /// An opaque tilemap cel at the top left corner, `width` tiles wide.
+    pub fn tilemap(layer: u16, width: u16, tiles: Vec<u32>) -> Self {
+        Self {
+            layer,
+            x: 0,
+            y: 0,
+            opacity: 255,
+            content: NewCelContent::Tilemap { width, tiles },
+            user_data: None,
+        }
+    }
+
+    /// A cel linked to the cel of the same layer in `frame`.
+    pub fn linked(layer: u16, frame: u16) -> Self {
+        Self {
+            layer,
+            x: 0,
+            y: 0,
+            opacity: 255,
+            content: NewCelContent::Linked(frame),
+            user_data: None,
+        }
+    }
+}
+
+/// A named range of frames.
+#[derive(Debug, Clone)]
+pub struct NewTag {
+    pub name: String,
+    pub from_frame: u16,
+    pub to_frame: u16,
+    pub animation_direction: AnimationDirection,
+    /// How many times the range is played, 0 repeats forever.
+    pub repeat: u16,
+    pub user_data: Option<UserData>,
+}
+
+impl NewTag {
+    /// A tag played forward forever.
+    pub fn new(name: &str, from_frame: u16, to_frame: u16) -> Self {
+        Self {
+            name: name.to_owned(),
+            from_frame,
+            to_frame,
+            animation_direction: AnimationDirection::Forward,
+            repeat: 0,
+            user_data: None,
+        }
+    }
+}
+
+const HEADER_SIZE: usize = 128;
+const FRAME_HEADER_SIZE: usize = 16;
+
+impl AsepriteWriter {
+    /// An empty RGBA file.
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            pixel_format: PixelFormat::Rgba,
+            palette: Vec::new(),
+            tilesets: Vec::new(),
+            layers: Vec::new(),
+            frames: Vec::new(),
+            tags: Vec::new(),
+            slices: Vec::new(),
+            user_data: None,
+        }
+    }
+
+    /// Write the file to `path`.
+    pub fn write_file(&self, path: &Path) -> Result<()> {
+        let mut data = Vec::new();
+        self.write(&mut data)?;
+        File::create(path)?.write_all(&data)?;
+        Ok(())
+    }
+
+    /// Write the file to any output that implements `std::io::Write`.
+    pub fn write<W: Write>(&self, mut output: W) -> Result<()> {
+        self.validate()?;
+
+        let mut frames = Vec::new();
+        for (index, frame) in self.frames.iter().enumerate() {
+            let mut chunks = Vec::new();
+            if index == 0 {
+                // Layers and the palette only appear in the first frame.
+                if !self.palette.is_empty() {
+                    chunks.push(chunk(0x2019, &self.palette_chunk()?)?);
+                }
+                // Sprite user data follows an old palette chunk, which is
+                // ignored when there's a new one.
+                if let Some(user_data) = &self.user_data {
+                    chunks.push(chunk(0x0004, &self.old_palette_chunk()?)?);
+                    chunks.push(chunk(0x2020, &user_data_chunk(user_data)?)?);
+                }
+                for (id, tileset) in self.tilesets.iter().enumerate() {
+                    chunks.push(chunk(0x2023, &self.tileset_chunk(id as u32, tileset)?)?);
+                }
+                for layer in &self.layers {
+                    chunks.push(chunk(0x2004, &layer_chunk(layer)?)?);
+                    if let Some(user_data) = &layer.user_data {
+                        chunks.push(chunk(0x2020, &user_data_chunk(user_data)?)?);
+                    }
+                }
+                if !self.tags.is_empty() {
+                    chunks.push(chunk(0x2018, &self.tags_chunk()?)?);
+                }
+                // Tag user data is matched to the tags in order, so every tag
+                // gets a chunk once any of them has user data.
+                if self.tags.iter().any(|t| t.user_data.is_some()) {
+                    for tag in &self.tags {
+                        let user_data = tag.user_data.clone().unwrap_or(UserData {
+                            text: None,
+                            color: None,
+                        });
+                        chunks.push(chunk(0x2020, &user_data_chunk(&user_data)?)?);
+                    }
+                }
+                for slice in &self.slices {
+                    chunks.push(chunk(0x2022, &slice_chunk(slice)?)?);
+                    if let Some(user_data) = &slice.user_data {
+                        chunks.push(chunk(0x2020, &user_data_chunk(user_data)?)?);
+                    }
+                }
+            }
+            for cel in &frame.cels {
+                chunks.push(chunk(0x2005, &self.cel_chunk(cel)?)?);
+                if let Some(user_data) = &cel.user_data {
+                    chunks.push(chunk(0x2020, &user_data_chunk(user_data)?)?);
+                }
+            }
+            frames.push(frame_bytes(frame.duration, &chunks)?);
+        }
+
+        let size = HEADER_SIZE + frames.iter().map(Vec::len).sum::<usize>();
+        let size = u32::try_from(size)
+            .map_err(|_| AsepriteParseError::InvalidInput("File is too large".to_owned()))?;
+        output.write_all(&self.header(size)?)?;
+        for frame in frames {
+            output.write_all(&frame)?;
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        let invalid = |msg: String| Err(AsepriteParseError::InvalidInput(msg));
+
+        if self.frames.is_empty() {
+            return invalid("A file needs at least one frame".to_owned());
+        }
+        match self.pixel_format {
+            PixelFormat::Rgba => {}
+            PixelFormat::Indexed {
+                transparent_color_index,
+            } => {
+                if self.palette.len() > 256 {
+                    return invalid(format!(
+                        "Indexed files have at most 256 colors, found {}",
+                        self.palette.len()
+                    ));
+                }
+                if usize::from(transparent_color_index) >= self.palette.len() {
+                    return invalid(format!(
+                        "Transparent color index {} is outside the palette",
+                        transparent_color_index
+                    ));
+                }
+            }
+            PixelFormat::Grayscale => {}
+        }
+
+        for tileset in &self.tilesets {
+            let size = (
+                u32::from(tileset.tile_width),
+                u32::from(tileset.tile_height),
+            );
+            if tileset.tiles.iter().any(|t| t.dimensions() != size) {
+                return invalid(format!(
+                    "Tileset {} has tiles that aren't {}x{}",
+                    tileset.name, size.0, size.1
+                ));
+            }
+        }
+
+        for layer in &self.layers {
+            if let Some(tileset) = layer.tileset {
+                if layer.is_group || tileset as usize >= self.tilesets.len() {
+                    return invalid(format!(
+                        "Layer {} uses missing tileset {}",
+                        layer.name, tileset
+                    ));
+                }
+            }
+        }
+
+        for tag in &self.tags {
+            if tag.from_frame > tag.to_frame || usize::from(tag.to_frame) >= self.frames.len() {
+                return invalid(format!(
+                    "Tag {} covers frames {}-{} outside the {} frames",
+                    tag.name,
+                    tag.from_frame,
+                    tag.to_frame,
+                    self.frames.len()
+                ));
+            }
+        }
+
+        for slice in &self.slices {
+            let nine = |k: &crate::SliceKey| k.slice9.is_some();
+            let pivot = |k: &crate::SliceKey| k.pivot.is_some();
+            // The flags are shared by every key of a slice.
+            if let Some(first) = slice.keys.first() {
+                if slice
+                    .keys
+                    .iter()
+                    .any(|k| nine(k) != nine(first) || pivot(k) != pivot(first))
+                {
+                    return invalid(format!(
+                        "Slice {} keys must all have 9-slice and pivot data, or none",
+                        slice.name
+                    ));
+                }
+            }
+        }
+
+        for (index, frame) in self.frames.iter().enumerate() {
+            for cel in &frame.cels {
+                let Some(layer) = self.layers.get(usize::from(cel.layer)) else {
+                    return invalid(format!(
+                        "Cel in frame {} references missing layer {}",
+                        index, cel.layer
+                    ));
+                };
+                if layer.is_group {
+                    return invalid(format!("Cel in frame {} is on a group layer", index));
+                }
+                match (&cel.content, layer.tileset) {
+                    (NewCelContent::Linked(other), _) => {
+                        let target = self
+                            .frames
+                            .get(usize::from(*other))
+                            .and_then(|f| f.cels.iter().find(|c| c.layer == cel.layer));
+                        if matches!(
+                            target.map(|c| &c.content),
+                            None | Some(NewCelContent::Linked(_))
+                        ) {
+                            return invalid(format!(
+                                "Cel in frame {} links to frame {} which has no image on layer {}",
+                                index, other, cel.layer
+                            ));
+                        }
+                    }
+                    (NewCelContent::Image(_), None) => {}
+                    (NewCelContent::Tilemap { width, tiles }, Some(tileset)) => {
+                        let count = self.tilesets[tileset as usize].tiles.len();
+                        if *width == 0 || tiles.len() % usize::from(*width) != 0 {
+                            return invalid(format!(
+                                "Tilemap in frame {} has {} tiles, which don't fill rows of {}",
+                                index,
+                                tiles.len(),
+                                width
+                            ));
+                        }
+                        if let Some(tile) = tiles.iter().find(|t| **t as usize > count) {
+                            return invalid(format!(
+                                "Tilemap in frame {} uses missing tile {}",
+                                index, tile
+                            ));
+                        }
+                    }
+                    (NewCelContent::Image(_), Some(_)) => {
+                        return invalid(format!(
+                            "Cel in frame {} is an image on a tilemap layer",
+                            index
+                        ));
+                    }
+                    (NewCelContent::Tilemap { .. }, None) => {
+                        return invalid(format!(
+                            "Cel in frame {} is a tilemap on an image layer",
+                            index
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn header(&self, file_size: u32) -> Result<Vec<u8>> {
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.write_u32::<LittleEndian>(file_size)?;
+        header.write_u16::<LittleEndian>(0xA5E0)?;
+        header.write_u16::<LittleEndian>(self.frames.len() as u16)?;
+        header.write_u16::<LittleEndian>(self.width)?;
+        header.write_u16::<LittleEndian>(self.height)?;
+        header.write_u16::<LittleEndian>(match self.pixel_format {
+            PixelFormat::Rgba => 32,
+            PixelFormat::Grayscale => 16,
+            PixelFormat::Indexed { .. } => 8,
+        })?;
+        // Layer opacity is valid.
+        header.write_u32::<LittleEndian>(1)?;
+        // Deprecated speed, frames have their own duration.
+        header.write_u16::<LittleEndian>(self.frames[0].duration)?;
+        header.write_u32::<LittleEndian>(0)?;
+        header.write_u32::<LittleEndian>(0)?;
+        header.write_u8(self.pixel_format.transparent_color_index().unwrap_or(0))?;
+        header.write_all(&[0; 3])?;
+        header.write_u16::<LittleEndian>(self.palette.len().min(256) as u16)?;
+        // 1:1 pixel ratio.
+        header.write_u8(1)?;
+        header.write_u8(1)?;
+        // Grid position and size.
+        header.write_i16::<LittleEndian>(0)?;
+        header.write_i16::<LittleEndian>(0)?;
+        header.write_u16::<LittleEndian>(16)?;
+        header.write_u16::<LittleEndian>(16)?;
+        header.write_all(&[0; 84])?;
+        Ok(header)
+    }
+
+    fn palette_chunk(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let count = self.palette.len() as u32;
+        data.write_u32::<LittleEndian>(count)?;
+        data.write_u32::<LittleEndian>(0)?;
+        data.write_u32::<LittleEndian>(count - 1)?;
+        data.write_all(&[0; 8])?;
+        for color in &self.palette {
+            data.write_u16::<LittleEndian>(u16::from(color.name.is_some()))?;
+            data.write_all(&color.rgba)?;
+            if let Some(name) = &color.name {
+                write_string(&mut data, name)?;
+            }
+        }
+        Ok(data)
+    }
+
+    fn old_palette_chunk(&self) -> Result<Vec<u8>> {
+        let colors = match self.palette.len() {
+            0 => vec![[0, 0, 0, 255]],
+            _ => self.palette.iter().take(256).map(|c| c.rgba).collect(),
+        };
+        let mut data = Vec::new();
+        // A single packet, 0 meaning 256 colors.
+        data.write_u16::<LittleEndian>(1)?;
+        data.write_u8(0)?;
+        data.write_u8(colors.len() as u8)?;
+        for [r, g, b, _] in colors {
+            data.write_all(&[r, g, b])?;
+        }
+        Ok(data)
+    }
+
+    fn tags_chunk(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        data.write_u16::<LittleEndian>(self.tags.len() as u16)?;
+        data.write_all(&[0; 8])?;
+        for tag in &self.tags {
+            data.write_u16::<LittleEndian>(tag.from_frame)?;
+            data.write_u16::<LittleEndian>(tag.to_frame)?;
+            data.write_u8(match tag.animation_direction {
+                AnimationDirection::Forward => 0,
+                AnimationDirection::Reverse => 1,
+                AnimationDirection::PingPong => 2,
+            })?;
+            data.write_u16::<LittleEndian>(tag.repeat)?;
+            data.write_all(&[0; 6])?;
+            // Deprecated color and extra byte.
+            data.write_all(&[0; 4])?;
+            write_string(&mut data, &tag.name)?;
+        }
+        Ok(data)
+    }
+
+    fn cel_chunk(&self, cel: &NewCel) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        data.write_u16::<LittleEndian>(cel.layer)?;
+        data.write_i16::<LittleEndian>(cel.x)?;
+        data.write_i16::<LittleEndian>(cel.y)?;
+        data.write_u8(cel.opacity)?;
+        match &cel.content {
+            NewCelContent::Image(image) => {
+                data.write_u16::<LittleEndian>(2)?;
+                // z-index and reserved bytes
+                data.write_all(&[0; 7])?;
+                let (width, height) = image.dimensions();
+                let size = |n: u32| {
+                    u16::try_from(n).map_err(|_| {
+                        AsepriteParseError::InvalidInput(format!("Cel is too large: {}", n))
+                    })
+                };
+                data.write_u16::<LittleEndian>(size(width)?)?;
+                data.write_u16::<LittleEndian>(size(height)?)?;
+
+                let mut encoder = ZlibEncoder::new(data, Compression::default());
+                encoder.write_all(&self.pixel_bytes(image)?)?;
+                data = encoder.finish()?;
+            }
+            NewCelContent::Linked(frame) => {
+                data.write_u16::<LittleEndian>(1)?;
+                data.write_all(&[0; 7])?;
+                data.write_u16::<LittleEndian>(*frame)?;
+            }
+            NewCelContent::Tilemap { width, tiles } => {
+                data.write_u16::<LittleEndian>(3)?;
+                data.write_all(&[0; 7])?;
+                data.write_u16::<LittleEndian>(*width)?;
+                data.write_u16::<LittleEndian>((tiles.len() / usize::from(*width)) as u16)?;
+                // 32 bits per tile, with masks for the id and the unused
+                // flip and rotation flags
+                data.write_u16::<LittleEndian>(32)?;
+                data.write_u32::<LittleEndian>(0x1fff_ffff)?;
+                data.write_u32::<LittleEndian>(0x2000_0000)?;
+                data.write_u32::<LittleEndian>(0x4000_0000)?;
+                data.write_u32::<LittleEndian>(0x8000_0000)?;
+                data.write_all(&[0; 10])?;
+
+                let mut encoder = ZlibEncoder::new(data, Compression::default());
+                for tile in tiles {
+                    encoder.write_u32::<LittleEndian>(*tile)?;
+                }
+                data = encoder.finish()?;
+            }
+        }
+        Ok(data)
+    }
+
+    fn tileset_chunk(&self, id: u32, tileset: &NewTileset) -> Result<Vec<u8>> {
+        let (width, height) = (tileset.tile_width, tileset.tile_height);
+        // The tiles are stacked in a single image, below the empty tile.
+        let mut image = RgbaImage::new(
+            u32::from(width),
+            u32::from(height) * (tileset.tiles.len() as u32 + 1),
+        );
+        for (i, tile) in tileset.tiles.iter().enumerate() {
+            image::imageops::replace(&mut image, tile, 0, i64::from(height) * (i as i64 + 1));
+        }
+        let mut pixels = ZlibEncoder::new(Vec::new(), Compression::default());
+        pixels.write_all(&self.pixel_bytes(&image)?)?;
+        let pixels = pixels.finish()?;
+
+        let mut data = Vec::new();
+        data.write_u32::<LittleEndian>(id)?;
+        // Tiles are included in the file, and tile 0 is empty.
+        data.write_u32::<LittleEndian>(0x0002 | 0x0004)?;
+        data.write_u32::<LittleEndian>(tileset.tiles.len() as u32 + 1)?;
+        data.write_u16::<LittleEndian>(width)?;
+        data.write_u16::<LittleEndian>(height)?;
+        // Tiles are numbered from 1 in the UI.
+        data.write_i16::<LittleEndian>(1)?;
+        data.write_all(&[0; 14])?;
+        write_string(&mut data, &tileset.name)?;
+        data.write_u32::<LittleEndian>(pixels.len() as u32)?;
+        data.write_all(&pixels)?;
+        Ok(data)
+    }
+
+    fn pixel_bytes(&self, image: &RgbaImage) -> Result<Vec<u8>> {
+        let transparent_color_index = match self.pixel_format {
+            PixelFormat::Rgba => return Ok(image.as_raw().clone()),
+            PixelFormat::Grayscale => {
+                return image
+                    .pixels()
+                    .map(|pixel| match pixel.0 {
+                        [_, _, _, 0] => Ok([0, 0]),
+                        [r, g, b, a] if r == g && g == b => Ok([r, a]),
+                        rgba => Err(AsepriteParseError::InvalidInput(format!(
+                            "Color {:?} is not gray",
+                            rgba
+                        ))),
+                    })
+                    .collect::<Result<Vec<_>>>()
+                    .map(|pixels| pixels.concat());
+            }
+            PixelFormat::Indexed {
+                transparent_color_index,
+            } => transparent_color_index,
+        };
+
+        // The first entry wins when the palette has duplicates.
+        let mut indices = HashMap::new();
+        for (index, color) in self.palette.iter().enumerate().rev() {
+            indices.insert(color.rgba, index as u8);
+        }
+        image
+            .pixels()
+            .map(|pixel| match indices.get(&pixel.0) {
+                Some(index) => Ok(*index),
+                None if pixel.0[3] == 0 => Ok(transparent_color_index),
+                None => Err(AsepriteParseError::InvalidInput(format!(
+                    "Color {:?} is not in the palette",
+                    pixel.0
+                ))),
+            })
+            .collect()
+    }
+}
+
+fn layer_chunk(layer: &NewLayer) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    data.write_u16::<LittleEndian>(layer.flags.bits() as u16)?;
+    data.write_u16::<LittleEndian>(match (layer.is_group, layer.tileset) {
+        (true, _) => 1,
+        (false, None) => 0,
+        (false, Some(_)) => 2,
+    })?;
+    data.write_u16::<LittleEndian>(layer.child_level)?;
+    // Ignored default width and height.
+    data.write_u16::<LittleEndian>(0)?;
+    data.write_u16::<LittleEndian>(0)?;
+    data.write_u16::<LittleEndian>(blend_mode_id(layer.blend_mode))?;
+    data.write_u8(layer.opacity)?;
+    data.write_all(&[0; 3])?;
+    write_string(&mut data, &layer.name)?;
+    if let Some(tileset) = layer.tileset {
+        data.write_u32::<LittleEndian>(tileset)?;
+    }
+    Ok(data)
+}
+
+fn blend_mode_id(blend_mode: BlendMode) -> u16 {
+    match blend_mode {
+        BlendMode::Normal => 0,
+        BlendMode::Multiply => 1,
+        BlendMode::Screen => 2,
+        BlendMode::Overlay => 3,
+        BlendMode::Darken => 4,
+        BlendMode::Lighten => 5,
+        BlendMode::ColorDodge => 6,
+        BlendMode::ColorBurn => 7,
+        BlendMode::HardLight => 8,
+        BlendMode::SoftLight => 9,
+        BlendMode::Difference => 10,
+        BlendMode::Exclusion => 11,
+        BlendMode::Hue => 12,
+        BlendMode::Saturation => 13,
+        BlendMode::Color => 14,
+        BlendMode::Luminosity => 15,
+        BlendMode::Addition => 16,
+        BlendMode::Subtract => 17,
+        BlendMode::Divide => 18,
+    }
+}
+
+fn user_data_chunk(user_data: &UserData) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let flags = u32::from(user_data.text.is_some()) | u32::from(user_data.color.is_some()) << 1;
+    data.write_u32::<LittleEndian>(flags)?;
+    if let Some(text) = &user_data.text {
+        write_string(&mut data, text)?;
+    }
+    if let Some(color) = user_data.color {
+        data.write_all(&color.0)?;
+    }
+    Ok(data)
+}
+
+fn slice_chunk(slice: &Slice) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let first = slice.keys.first();
+    let nine = first.is_some_and(|k| k.slice9.is_some());
+    let pivot = first.is_some_and(|k| k.pivot.is_some());
+    data.write_u32::<LittleEndian>(slice.keys.len() as u32)?;
+    data.write_u32::<LittleEndian>(u32::from(nine) | u32::from(pivot) << 1)?;
+    data.write_u32::<LittleEndian>(0)?;
+    write_string(&mut data, &slice.name)?;
+    for key in &slice.keys {
+        data.write_u32::<LittleEndian>(key.from_frame)?;
+        data.write_i32::<LittleEndian>(key.origin.0)?;
+        data.write_i32::<LittleEndian>(key.origin.1)?;
+        data.write_u32::<LittleEndian>(key.size.0)?;
+        data.write_u32::<LittleEndian>(key.size.1)?;
+        if let Some(slice9) = &key.slice9 {
+            data.write_i32::<LittleEndian>(slice9.center_x)?;
+            data.write_i32::<LittleEndian>(slice9.center_y)?;
+            data.write_u32::<LittleEndian>(slice9.center_width)?;
+            data.write_u32::<LittleEndian>(slice9.center_height)?;
+        }
+        if let Some((x, y)) = key.pivot {
+            data.write_i32::<LittleEndian>(x)?;
+            data.write_i32::<LittleEndian>(y)?;
+        }
+    }
+    Ok(data)
+}
+
+fn write_string(data: &mut Vec<u8>, s: &str) -> Result<()> {
+    let len = u16::try_from(s.len())
+        .map_err(|_| AsepriteParseError::InvalidInput(format!("String is too long: {}", s)))?;
+    data.write_u16::<LittleEndian>(len)?;
+    data.write_all(s.as_bytes())?;
+    Ok(())
+}
+
+fn chunk(chunk_type: u16, data: &[u8]) -> Result<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(data.len() + 6);
+    chunk.write_u32::<LittleEndian>(data.len() as u32 + 6)?;
+    chunk.write_u16::<LittleEndian>(chunk_type)?;
+    chunk.write_all(data)?;
+    Ok(chunk)
+}
+
+fn frame_bytes(duration: u16, chunks: &[Vec<u8>]) -> Result<Vec<u8>> {
+    let size = FRAME_HEADER_SIZE + chunks.iter().map(Vec::len).sum::<usize>();
+    let mut frame = Vec::with_capacity(size);
+    frame.write_u32::<LittleEndian>(size as u32)?;
+    frame.write_u16::<LittleEndian>(0xF1FA)?;
+    frame.write_u16::<LittleEndian>(chunks.len().min(0xFFFF) as u16)?;
+    frame.write_u16::<LittleEndian>(duration)?;
+    frame.write_all(&[0; 2])?;
+    frame.write_u32::<LittleEndian>(chunks.len() as u32)?;
+    for chunk in chunks {
+        frame.write_all(chunk)?;
+    }
+    Ok(frame)
+}
@@ -4,121 +4,1007 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::collections::{HashMap, HashSet};
-use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+use image::{Rgba, RgbaImage};
+use indexmap::{IndexMap, IndexSet};
 use mabel_aseprite::{
-    cel::{CelContent, Pixels},
-    AsepriteFile,
+    cel::{CelContent, RawCel},
+    writer::{AsepriteWriter, NewCel, NewColor, NewFrame, NewLayer},
+    AnimationDirection, AsepriteFile, LayerFlags, LayerType, PixelFormat, UserData,
 };
 
-use crate::Result;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-pub fn save_to_eno(ase_path: &str, output_path: &str) -> Result<()> {
-    let ase = AsepriteFile::read_file(Path::new(ase_path))?;
+use crate::{
+    layer::{blend_mode_name, BlendMode},
+    palette::{format_color, key_width_with, key_with, Notation, KEYS},
+    Error, Mabel, Pixel, Result,
+};
+
+/// Which frames of an aseprite file are converted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Frames {
+    #[default]
+    All,
+    /// The frames from the first to the last index, inclusive.
+    Range(u32, u32),
+    /// The frames of the tag with this name.
+    Tag(String),
+}
 
-    if ase.num_frames() != 1 {
-        return Err(format!(
-            "Expected 1 frame in aseprite file, found {} frames",
-            ase.num_frames()
-        )
-        .into());
+impl Frames {
+    /// The frame indices in `ase` to convert, in order.
+    pub fn resolve(&self, ase: &AsepriteFile) -> Result<Vec<u32>> {
+        match self {
+            Self::All => Ok((0..ase.num_frames()).collect()),
+            Self::Range(first, last) if first > last || *last >= ase.num_frames() => Err(format!(
+                "Frame range {first}-{last} is outside the {} frames of the file",
+                ase.num_frames()
+            )
+            .into()),
+            Self::Range(first, last) => Ok((*first..=*last).collect()),
+            Self::Tag(name) => {
+                let tag = ase
+                    .tag_by_name(name)
+                    .ok_or_else(|| format!("Unknown tag \"{name}\""))?;
+                Ok((tag.from_frame()..=tag.to_frame()).collect())
+            }
+        }
     }
+}
 
-    let mut image_pixels: Vec<Vec<[u8; 4]>> = vec![vec![[0, 0, 0, 0]; ase.width()]; ase.height()];
+impl FromStr for Frames {
+    type Err = Error;
 
-    for layer in ase.layers() {
-        if !layer.is_visible() {
-            continue;
+    fn from_str(s: &str) -> Result<Self> {
+        let frame = |f: &str| f.trim().parse::<u32>().ok();
+        let range = match s.split_once('-') {
+            _ if s == "all" => return Ok(Self::All),
+            Some((first, last)) => frame(first).zip(frame(last)),
+            None => frame(s).map(|f| (f, f)),
+        };
+
+        range
+            .map(|(first, last)| Self::Range(first, last))
+            .ok_or_else(|| {
+                format!("Invalid frames \"{s}\", must be all, a frame or first-last").into()
+            })
+    }
+}
+
+/// The order of the palette of a converted file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Order {
+    /// The palette of indexed files in index order, followed by the other
+    /// colors in the order they appear.
+    #[default]
+    Palette,
+    /// The colors in the order they appear, frame by frame from the top-left,
+    /// followed by any unused palette colors.
+    Appearance,
+    /// The colors from darkest to lightest.
+    Luminance,
+}
+
+impl FromStr for Order {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "palette" => Ok(Self::Palette),
+            "appearance" => Ok(Self::Appearance),
+            "luminance" => Ok(Self::Luminance),
+            _ => Err(
+                format!("Invalid order \"{s}\", must be palette, appearance or luminance").into(),
+            ),
         }
+    }
+}
 
-        let cel = ase.cel(0, layer.id());
-        let raw_cel = cel.raw_cel().unwrap();
-        let (starting_x, starting_y) = cel.top_left();
+/// How the palette keys of a converted file are picked.
+#[derive(Debug, Clone, Default)]
+pub struct KeyOptions {
+    pub order: Order,
+    /// Picks keys that describe the colors where possible, like `r` for
+    /// reds, `k` for black and `w` for white.
+    pub smart: bool,
+    /// The characters keys are made of, instead of `0-9a-zA-Z`.
+    pub alphabet: Option<String>,
+}
 
-        match &raw_cel.content {
-            CelContent::Raw(raw) => {
-                let pixels = &raw.pixels;
-                let cel_width = raw.size.width;
-                let cel_height = raw.size.height;
-                let (ending_x, ending_y) = (
-                    starting_x + i32::from(cel_width),
-                    starting_y + i32::from(cel_height),
-                );
-                match pixels {
-                    Pixels::Rgba(pixels) => {
-                        let mut pixels = pixels.iter();
-
-                        for y in starting_y..ending_y {
-                            for x in starting_x..ending_x {
-                                let new_pixel = pixels.next().unwrap().0;
-                                if let Some(old_pixel) =
-                                    image_pixels[y as usize].get_mut(x as usize)
-                                {
-                                    if new_pixel[3] == 0 && old_pixel[3] != 0 {
-                                        continue;
-                                    }
-                                    *old_pixel = new_pixel;
-                                }
-                            }
-                        }
+impl KeyOptions {
+    fn alphabet(&self) -> Result<Vec<&str>> {
+        let Some(alphabet) = &self.alphabet else {
+            return Ok(KEYS.graphemes(true).collect());
+        };
+
+        let mut letters = vec![];
+        for letter in alphabet.graphemes(true) {
+            // anything that could be read as eno syntax
+            if letter.trim().len() != letter.len()
+                || letter.starts_with(['=', ':', '>', '#', '-', '`', '|', '\\', '<'])
+            {
+                return Err(format!("Invalid key \"{letter}\" in the key alphabet").into());
+            }
+            if letters.contains(&letter) {
+                return Err(format!("Duplicate key \"{letter}\" in the key alphabet").into());
+            }
+            letters.push(letter);
+        }
+
+        if letters.is_empty() {
+            return Err("The key alphabet is empty".into());
+        }
+
+        Ok(letters)
+    }
+}
+
+/// The pixels of a frame, row by row.
+type Image = Vec<Vec<[u8; 4]>>;
+
+/// What's drawn in a converted frame.
+enum Content {
+    /// The visible layers drawn over each other.
+    Flat(Image),
+    /// Every image layer on its own, from bottom to top.
+    Layers(Vec<LayerImage>),
+}
+
+impl Content {
+    fn images(&self) -> Vec<&Image> {
+        match self {
+            Self::Flat(image) => vec![image],
+            Self::Layers(layers) => layers.iter().map(|l| &l.image).collect(),
+        }
+    }
+
+    fn images_mut(&mut self) -> Vec<&mut Image> {
+        match self {
+            Self::Flat(image) => vec![image],
+            Self::Layers(layers) => layers.iter_mut().map(|l| &mut l.image).collect(),
+        }
+    }
+}
+
+/// A layer of a frame and its pixels.
+struct LayerImage {
+    name: String,
+    /// The groups the layer is nested in, as `outer/inner`.
+    group: Option<String>,
+    opacity: u8,
+    /// Whether the layer and all of its groups are visible.
+    visible: bool,
+    blend_mode: BlendMode,
+    user_data: Option<UserData>,
+    /// The user data of the layer's cel in this frame.
+    cel_data: Option<UserData>,
+    image: Image,
+}
+
+/// The pixel size of a converted document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelSize {
+    Fixed(u8),
+    /// The largest size that keeps the image at most this many pixels wide.
+    Width(u32),
+}
+
+impl Default for PixelSize {
+    fn default() -> Self {
+        Self::Fixed(32)
+    }
+}
+
+/// How an aseprite file is converted into eno.
+#[derive(Debug, Clone, Default)]
+pub struct ConvertOptions {
+    pub frames: Frames,
+    /// Write every frame to its own document.
+    pub flat: bool,
+    /// Keep every layer as a `layer` section, hidden ones included, instead
+    /// of drawing the visible layers over each other.
+    pub layers: bool,
+    /// Have every opaque pixel replace the ones below it, instead of drawing
+    /// the layers the way aseprite does with their blend modes and opacities.
+    pub raw_layers: bool,
+    /// The order and keys of the palette.
+    pub keys: KeyOptions,
+    pub size: PixelSize,
+    /// How the palette colors are written.
+    pub notation: Notation,
+    /// Write the `width` and `height` of the image.
+    pub dimensions: bool,
+    /// Crop the transparent borders shared by every frame.
+    pub trim: bool,
+}
+
+/// Converts an aseprite file into an eno document, see [`to_eno`]. Flat
+/// documents are named after the output with the frame index appended, e.g.
+/// `walk-0.eno`.
+pub fn save_to_eno(ase_path: &str, output_path: &str, options: &ConvertOptions) -> Result<()> {
+    let ase = AsepriteFile::read_file(Path::new(ase_path))?;
+    let documents = to_eno(&ase, options)?;
+
+    if options.flat {
+        let indices = options.frames.resolve(&ase)?;
+        for (index, document) in indices.iter().zip(documents) {
+            std::fs::write(flat_path(output_path, *index), document)?;
+        }
+    } else {
+        std::fs::write(output_path, documents.concat())?;
+    }
+
+    Ok(())
+}
+
+/// The frames of an aseprite file as an eno document, or a document for each
+/// frame with `flat`.
+pub fn to_eno(ase: &AsepriteFile, options: &ConvertOptions) -> Result<Vec<String>> {
+    let indices = options.frames.resolve(ase)?;
+    let mut contents = indices
+        .iter()
+        .map(|i| match (options.layers, options.raw_layers) {
+            (true, _) => frame_layers(ase, *i).map(Content::Layers),
+            (false, true) => frame_pixels(ase, *i).map(Content::Flat),
+            (false, false) => Ok(Content::Flat(frame_image(ase, *i))),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let offset = match options.trim {
+        true => trim(&mut contents),
+        false => (0, 0),
+    };
+
+    // the frames share a palette, so keys are the same across flat documents
+    let keys = Keys::new(&contents, &palette(ase), &options.keys)?;
+    let width = contents
+        .first()
+        .and_then(|c| c.images().first().and_then(|i| i.first()).map(Vec::len))
+        .unwrap_or(0) as u32;
+    let size = match options.size {
+        PixelSize::Fixed(size) => size,
+        PixelSize::Width(target) => (target / width.max(1)).clamp(1, 255) as u8,
+    };
+
+    if options.flat {
+        Ok(contents
+            .iter()
+            .zip(&indices)
+            .map(|(content, index)| {
+                let metadata = Metadata::new(ase, &[*index], offset, options);
+                let duration = ase.frame(*index).duration();
+                keys.document(&[(content, Some(duration))], size, &metadata, options)
+            })
+            .collect())
+    } else {
+        let durations = indices.iter().map(|i| ase.frame(*i).duration());
+        let frames = contents.iter().zip(durations.map(Some)).collect::<Vec<_>>();
+        let metadata = Metadata::new(ase, &indices, offset, options);
+        Ok(vec![keys.document(&frames, size, &metadata, options)])
+    }
+}
+
+/// Crops the transparent borders shared by every image, returning how far
+/// the images moved left and up.
+fn trim(contents: &mut [Content]) -> (usize, usize) {
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for image in contents.iter().flat_map(Content::images) {
+        for (y, row) in image.iter().enumerate() {
+            for (x, _) in row.iter().enumerate().filter(|(_, p)| p[3] != 0) {
+                bounds = Some(match bounds {
+                    Some((left, top, right, bottom)) => {
+                        (left.min(x), top.min(y), right.max(x), bottom.max(y))
                     }
-                    _ => return Err("Expected RGBA pixels".into()),
-                }
+                    None => (x, y, x, y),
+                });
+            }
+        }
+    }
+
+    // fully transparent frames are left as they are
+    let Some((left, top, right, bottom)) = bounds else {
+        return (0, 0);
+    };
+    for content in contents {
+        for image in content.images_mut() {
+            image.truncate(bottom + 1);
+            image.drain(..top);
+            for row in image {
+                row.truncate(right + 1);
+                row.drain(..left);
             }
-            _ => return Err("Expected raw cel content".into()),
         }
     }
 
-    let mut palette: HashSet<[u8; 4]> = HashSet::new();
+    (left, top)
+}
+
+/// The tags, slices and user data of an aseprite file, written as fields and
+/// sections that mabel itself doesn't draw.
+struct Metadata {
+    /// Fields of the whole document, written before the palette.
+    fields: String,
+    /// `tag` and `slice` sections, written after the frames.
+    sections: String,
+}
+
+impl Metadata {
+    /// The metadata of the frames in `indices`, with slices moved by the
+    /// `offset` of a trimmed image.
+    fn new(
+        ase: &AsepriteFile,
+        indices: &[u32],
+        (left, top): (usize, usize),
+        options: &ConvertOptions,
+    ) -> Self {
+        let notation = options.notation;
+        let fields = user_data_fields(ase.sprite_user_data(), "user", notation);
+        let mut sections = String::new();
+
+        // without layer sections to keep it in, the user data of layers and
+        // their cels gets sections of its own
+        if !options.layers {
+            for layer in ase.layers() {
+                let user_data = user_data_fields(layer.user_data(), "user", notation);
+                if !user_data.is_empty() {
+                    sections.push_str(&format!("\n# layer_data\nname: {}\n", layer.name()));
+                    sections.push_str(&user_data);
+                }
+
+                for (frame, index) in indices.iter().enumerate() {
+                    let cel = ase.cel(*index, layer.id());
+                    let cel_data = user_data_fields(cel.user_data(), "cel", notation);
+                    if cel_data.is_empty() {
+                        continue;
+                    }
+                    sections.push_str(&format!("\n# cel_data\nlayer: {}\n", layer.name()));
+                    if indices.len() > 1 {
+                        sections.push_str(&format!("frame: {frame}\n"));
+                    }
+                    sections.push_str(&cel_data);
+                }
+            }
+        }
 
-    for p in &image_pixels {
-        for y in p {
-            if y.eq(&[0, 0, 0, 0]) {
+        for tag in (0..ase.num_tags()).map(|i| ase.tag(i)) {
+            let range = tag.from_frame()..=tag.to_frame();
+            let from = indices.iter().position(|i| range.contains(i));
+            let to = indices.iter().rposition(|i| range.contains(i));
+            let (Some(from), Some(to)) = (from, to) else {
                 continue;
+            };
+
+            sections.push_str(&format!("\n# tag\nname: {}\n", tag.name()));
+            sections.push_str(&format!("from: {from}\nto: {to}\n"));
+            let direction = match tag.animation_direction() {
+                AnimationDirection::Forward => "forward",
+                AnimationDirection::Reverse => "reverse",
+                AnimationDirection::PingPong => "ping-pong",
+            };
+            sections.push_str(&format!("direction: {direction}\n"));
+            if let Some(repeat) = tag.repeat() {
+                sections.push_str(&format!("repeat: {repeat}\n"));
             }
+            sections.push_str(&user_data_fields(tag.user_data(), "user", notation));
+        }
+
+        for slice in ase.slices() {
+            // a key holds until the next one, so it's written where it first
+            // applies to the converted frames
+            let mut last = None;
+            for (frame, index) in indices.iter().enumerate() {
+                let active = slice.keys.iter().rposition(|k| k.from_frame <= *index);
+                let (Some(key), true) = (active, active != last) else {
+                    continue;
+                };
+                last = active;
+                let key = &slice.keys[key];
+                // empty keys hide the slice from their frame on
+                if key.size.0 == 0 || key.size.1 == 0 {
+                    continue;
+                }
+
+                sections.push_str(&format!("\n# slice\nname: {}\n", slice.name));
+                if indices.len() > 1 {
+                    sections.push_str(&format!("frame: {frame}\n"));
+                }
+                let (x, y) = (
+                    i64::from(key.origin.0) - left as i64,
+                    i64::from(key.origin.1) - top as i64,
+                );
+                sections.push_str(&format!("x: {x}\ny: {y}\n"));
+                sections.push_str(&format!("width: {}\nheight: {}\n", key.size.0, key.size.1));
+                if let Some(center) = &key.slice9 {
+                    sections.push_str(&format!(
+                        "center: {}, {}, {}, {}\n",
+                        center.center_x, center.center_y, center.center_width, center.center_height
+                    ));
+                }
+                if let Some((x, y)) = key.pivot {
+                    sections.push_str(&format!("pivot: {x}, {y}\n"));
+                }
+                sections.push_str(&user_data_fields(
+                    slice.user_data.as_ref(),
+                    "user",
+                    notation,
+                ));
+            }
+        }
+
+        Self { fields, sections }
+    }
+}
+
+/// The text and color of user data as `<prefix>_data` and `<prefix>_color`
+/// fields, with multiline text as an embed.
+fn user_data_fields(user_data: Option<&UserData>, prefix: &str, notation: Notation) -> String {
+    let mut fields = String::new();
+    let Some(user_data) = user_data else {
+        return fields;
+    };
+
+    match user_data.text.as_deref().map(str::trim) {
+        Some(text) if text.contains('\n') => {
+            fields.push_str(&format!("-- {prefix}_data\n{text}\n-- {prefix}_data\n"));
+        }
+        Some(text) if !text.is_empty() => fields.push_str(&format!("{prefix}_data: {text}\n")),
+        _ => {}
+    }
+    if let Some(color) = user_data.color {
+        fields.push_str(&format!(
+            "{prefix}_color: {}\n",
+            format_color(color.0, notation)
+        ));
+    }
+
+    fields
+}
+
+/// The palette of an indexed file in index order, without its transparent
+/// color. Other files are drawn in any colors, so their palette isn't used.
+fn palette(ase: &AsepriteFile) -> Vec<([u8; 4], Option<String>)> {
+    let (Some(palette), true) = (ase.palette(), ase.is_indexed_color()) else {
+        return vec![];
+    };
+
+    (0..palette.num_colors())
+        .filter(|i| Some(*i as u8) != ase.transparent_color_index())
+        .filter_map(|i| palette.color(i))
+        .map(|color| (color.raw_rgba8(), color.name().map(str::to_owned)))
+        .collect()
+}
+
+/// A frame as aseprite draws it.
+fn frame_image(ase: &AsepriteFile, frame: u32) -> Image {
+    ase.frame(frame)
+        .image()
+        .rows()
+        .map(|row| {
+            row.map(|p| match p.0 {
+                [_, _, _, 0] => [0, 0, 0, 0],
+                rgba => rgba,
+            })
+            .collect()
+        })
+        .collect()
+}
+
+/// The visible layers of a frame drawn over each other, without blending.
+fn frame_pixels(ase: &AsepriteFile, frame: u32) -> Result<Image> {
+    let mut image_pixels: Image = vec![vec![[0, 0, 0, 0]; ase.width()]; ase.height()];
+
+    for layer in ase.layers() {
+        if layer.is_visible() {
+            draw_cel(ase, frame, layer.id(), &mut image_pixels)?;
+        }
+    }
+
+    Ok(image_pixels)
+}
+
+/// Every image layer of a frame, including hidden ones, with their groups.
+fn frame_layers(ase: &AsepriteFile, frame: u32) -> Result<Vec<LayerImage>> {
+    let mut layers = vec![];
+
+    for layer in ase.layers() {
+        if layer.layer_type() == LayerType::Group {
+            continue;
+        }
+
+        let mut groups = vec![];
+        let mut parent = layer.parent().map(|p| p.id());
+        while let Some(id) = parent {
+            let group = ase.layer(id);
+            groups.push(group.name().to_owned());
+            parent = group.parent().map(|p| p.id());
+        }
+        groups.reverse();
+
+        let mut image = vec![vec![[0, 0, 0, 0]; ase.width()]; ase.height()];
+        draw_cel(ase, frame, layer.id(), &mut image)?;
+        layers.push(LayerImage {
+            name: layer.name().to_owned(),
+            group: (!groups.is_empty()).then(|| groups.join("/")),
+            // tilemaps are drawn with their opacity already, cels are drawn
+            // with both their own and the layer's
+            opacity: match layer.layer_type() {
+                LayerType::Tilemap(_) => 255,
+                _ => mul_opacity(layer.opacity(), cel_opacity(ase, frame, layer.id())),
+            },
+            visible: layer.is_visible(),
+            blend_mode: layer.blend_mode(),
+            user_data: layer.user_data().cloned(),
+            cel_data: ase.cel(frame, layer.id()).user_data().cloned(),
+            image,
+        });
+    }
+
+    Ok(layers)
+}
+
+/// The opacity of the cel of a layer, or of the cel it links to.
+fn cel_opacity(ase: &AsepriteFile, frame: u32, layer: u32) -> u8 {
+    match ase.cel(frame, layer).raw_cel() {
+        Some(RawCel {
+            content: CelContent::Linked(source),
+            ..
+        }) => cel_opacity(ase, u32::from(*source), layer),
+        Some(raw_cel) => raw_cel.data.opacity,
+        None => 255,
+    }
+}
+
+/// Two opacities applied over each other, rounded like Aseprite does.
+fn mul_opacity(a: u8, b: u8) -> u8 {
+    ((u32::from(a) * u32::from(b) + 127) / 255) as u8
+}
+
+/// Draws the cel of a layer over `image`, where its pixels aren't transparent.
+fn draw_cel(ase: &AsepriteFile, frame: u32, layer: u32, image: &mut Image) -> Result<()> {
+    let cel = ase.cel(frame, layer);
+    // layers can be empty in some frames
+    let Some(raw_cel) = cel.raw_cel() else {
+        return Ok(());
+    };
+
+    match &raw_cel.content {
+        CelContent::Raw(raw) => draw(
+            image,
+            cel.top_left(),
+            raw.size.width,
+            &raw.pixels.clone_as_image_rgba(),
+        ),
+        // linked cels show the cel of another frame
+        CelContent::Linked(source) => draw_cel(ase, u32::from(*source), layer, image)?,
+        CelContent::Tilemap(_) => {
+            let tilemap = ase.tilemap(layer, frame).ok_or_else(|| {
+                format!("Missing tileset for layer \"{}\"", ase.layer(layer).name())
+            })?;
+            // drawn over the whole canvas, with the layer opacity applied
+            let tiles = tilemap.image();
+            let pixels = tiles.pixels().copied().collect::<Vec<_>>();
+            draw(image, (0, 0), tiles.width() as u16, &pixels);
+        }
+    }
 
-            palette.insert(*y);
+    Ok(())
+}
+
+/// Draws `width` pixels wide rows of pixels over `image` at `(x, y)`.
+fn draw(image: &mut Image, (x, y): (i32, i32), width: u16, pixels: &[Rgba<u8>]) {
+    for (i, pixel) in pixels.iter().enumerate() {
+        // transparent pixels can keep any color
+        if pixel[3] == 0 {
+            continue;
+        }
+
+        let (column, row) = (i % usize::from(width), i / usize::from(width));
+        // cels can be moved partly off the canvas
+        if let Some(old_pixel) = usize::try_from(y + row as i32)
+            .ok()
+            .and_then(|y| image.get_mut(y))
+            .zip(usize::try_from(x + column as i32).ok())
+            .and_then(|(row, x)| row.get_mut(x))
+        {
+            *old_pixel = pixel.0;
         }
     }
+}
+
+/// The palette keys of every color in a set of frames.
+struct Keys {
+    width: usize,
+    colors: Vec<([u8; 4], String)>,
+    /// Names of palette entries that can't be used as keys, written as comments.
+    names: HashMap<[u8; 4], String>,
+}
+
+impl Keys {
+    /// Keys for the `palette` entries and the other colors of the frames, in
+    /// the order of `options`. Names are used as keys when they're as wide as
+    /// the other keys and made of the key alphabet.
+    fn new(
+        contents: &[Content],
+        palette: &[([u8; 4], Option<String>)],
+        options: &KeyOptions,
+    ) -> Result<Self> {
+        let alphabet = options.alphabet()?;
+        let mut palette = palette
+            .iter()
+            .filter(|(rgba, _)| rgba[3] != 0)
+            .map(|(rgba, name)| (*rgba, name.clone()))
+            .collect::<IndexMap<_, _>>();
+        let drawn = contents
+            .iter()
+            .flat_map(Content::images)
+            .flatten()
+            .flatten()
+            .filter(|rgba| **rgba != [0, 0, 0, 0])
+            .collect::<IndexSet<_>>();
 
-    let mut chars = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ".chars();
-    let mut palette_map = HashMap::new();
+        let colors = match options.order {
+            Order::Palette | Order::Luminance => {
+                for rgba in drawn {
+                    palette.entry(*rgba).or_default();
+                }
+                if options.order == Order::Luminance {
+                    palette.sort_by_cached_key(|rgba, _| luminance(*rgba));
+                }
+                palette
+            }
+            Order::Appearance => {
+                let mut colors = drawn
+                    .into_iter()
+                    .map(|rgba| (*rgba, palette.shift_remove(rgba).flatten()))
+                    .collect::<IndexMap<_, _>>();
+                colors.extend(palette);
+                colors
+            }
+        };
 
-    let mut eno = "size: 32\n".to_string();
-    if !palette.is_empty() {
-        eno.push_str("palette: \n");
-        for color in palette {
-            if color == [0, 0, 0, 0] {
+        let width = key_width_with(colors.len(), alphabet.len())?;
+        let mut used = HashSet::new();
+        let mut names = HashMap::new();
+        let mut keys = HashMap::new();
+        for (color, name) in &colors {
+            let Some(name) = name else {
                 continue;
+            };
+
+            let letters = name.graphemes(true).collect::<Vec<_>>();
+            if letters.len() == width
+                && letters.iter().all(|l| alphabet.contains(l))
+                && used.insert(name.clone())
+            {
+                keys.insert(*color, name.clone());
+            } else {
+                names.insert(*color, name.clone());
             }
+        }
+
+        if options.smart {
+            // the rest of a wider key tells colors of the same kind apart
+            let rest = alphabet.len().pow(width as u32 - 1);
+            for color in colors.keys() {
+                if keys.contains_key(color) {
+                    continue;
+                }
 
-            let char = chars.next().unwrap();
-            palette_map.insert(color, char);
-            eno.push_str(&format!(
-                "{char} = rgba({}, {}, {}, {})\n",
-                color[0],
-                color[1],
-                color[2],
-                f32::from(color[3]) / 255.0
-            ));
+                let key = mnemonics(*color)
+                    .into_iter()
+                    .filter(|m| alphabet.contains(m))
+                    .flat_map(|m| (0..rest).map(move |i| (m, i)))
+                    .map(|(m, i)| format!("{m}{}", key_with(i, width - 1, &alphabet)))
+                    .find(|key| !used.contains(key));
+                if let Some(key) = key {
+                    used.insert(key.clone());
+                    keys.insert(*color, key);
+                }
+            }
         }
+
+        let mut generated = (0..)
+            .map(|i| key_with(i, width, &alphabet))
+            .filter(|k| !used.contains(k));
+        let colors = colors
+            .keys()
+            .map(|color| {
+                let key = keys
+                    .remove(color)
+                    .unwrap_or_else(|| generated.next().unwrap());
+                (*color, key)
+            })
+            .collect();
+
+        Ok(Self {
+            width,
+            colors,
+            names,
+        })
     }
 
-    palette_map.insert([0, 0, 0, 0], ' ');
+    /// A document with the frames and their durations, with a single frame
+    /// written at the top level.
+    fn document(
+        &self,
+        frames: &[(&Content, Option<u32>)],
+        size: u8,
+        metadata: &Metadata,
+        options: &ConvertOptions,
+    ) -> String {
+        let mut palette_map = self.colors.iter().cloned().collect::<HashMap<_, _>>();
+        palette_map.insert([0, 0, 0, 0], " ".repeat(self.width));
+        // narrower keys are padded to the widest one, e.g. for emoji keys
+        let cell = palette_map.values().map(|k| k.width()).max().unwrap_or(1);
+        for key in palette_map.values_mut() {
+            key.push_str(&" ".repeat(cell - key.width()));
+        }
+
+        let mut eno = format!("size: {size}\n");
+        if let (true, Some((content, _))) = (options.dimensions, frames.first()) {
+            let image = content.images()[0];
+            let width = image.first().map_or(0, Vec::len);
+            eno.push_str(&format!("width: {width}\nheight: {}\n", image.len()));
+        }
+        if self.width > 1 {
+            eno.push_str(&format!("key_width: {}\n", self.width));
+        }
+        // a shared duration is written once for the document, and a flat
+        // document keeps the duration of its frame
+        let duration = frames.first().and_then(|(_, d)| *d);
+        let shared = frames.iter().all(|(_, d)| *d == duration);
+        if let (Some(duration), true, true) = (duration, shared, frames.len() > 1 || options.flat) {
+            eno.push_str(&format!("duration: {duration}\n"));
+        }
+        eno.push_str(&metadata.fields);
+        if !self.colors.is_empty() {
+            eno.push_str("palette: \n");
+            for (color, key) in &self.colors {
+                if let Some(name) = self.names.get(color) {
+                    eno.push_str(&format!("> {name}\n"));
+                }
+                eno.push_str(&format!(
+                    "{key} = {}\n",
+                    format_color(*color, options.notation)
+                ));
+            }
+        }
+
+        let pixels = |image: &Image| {
+            let mut pixels = "-- pixels\n".to_string();
+            for row in image {
+                for cell in row {
+                    pixels.push_str(&palette_map[cell]);
+                }
+
+                pixels.push('\n');
+            }
+            pixels.push_str("-- pixels");
+            pixels
+        };
+        // layers are subsections of frames, one level deeper
+        let content = |content: &Content, depth: &str| match content {
+            Content::Flat(image) => pixels(image),
+            Content::Layers(layers) => {
+                let mut sections = String::new();
+                for layer in layers {
+                    sections.push_str(&format!("\n{depth} layer\nname: {}\n", layer.name));
+                    if let Some(group) = &layer.group {
+                        sections.push_str(&format!("group: {group}\n"));
+                    }
+                    if layer.opacity != 255 {
+                        sections.push_str(&format!("opacity: {}\n", layer.opacity));
+                    }
+                    if !layer.visible {
+                        sections.push_str("visible: false\n");
+                    }
+                    if layer.blend_mode != BlendMode::Normal {
+                        sections
+                            .push_str(&format!("blend: {}\n", blend_mode_name(layer.blend_mode)));
+                    }
+                    let user_data = layer.user_data.as_ref();
+                    sections.push_str(&user_data_fields(user_data, "user", options.notation));
+                    let cel_data = layer.cel_data.as_ref();
+                    sections.push_str(&user_data_fields(cel_data, "cel", options.notation));
+                    sections.push_str(&pixels(&layer.image));
+                    sections.push('\n');
+                }
+                sections.trim().to_owned()
+            }
+        };
 
-    eno.push_str("\n-- pixels\n");
-    for row in image_pixels {
-        for cell in row {
-            eno.push(*palette_map.get(&cell).unwrap());
+        match frames {
+            [(frame, _)] => {
+                eno.push('\n');
+                eno.push_str(&content(frame, "#"));
+                eno.push('\n');
+            }
+            frames => {
+                for (frame, duration) in frames {
+                    eno.push_str("\n# frame\n");
+                    if let (Some(duration), false) = (duration, shared) {
+                        eno.push_str(&format!("duration: {duration}\n"));
+                    }
+                    eno.push_str(&content(frame, "##"));
+                    eno.push('\n');
+                }
+            }
         }
+        eno.push_str(&metadata.sections);
 
-        eno.push('\n');
+        eno
     }
-    eno.push_str("-- pixels");
+}
 
-    let mut output_file = std::fs::File::create(output_path)?;
-    output_file.write_all(eno.as_bytes())?;
+/// Keys that describe a color, best first: `k` for black, `w` for white, `s`
+/// for grays and the first letter of the hue for the rest, with `o`, `v` and
+/// `m` for orange, violet and magenta. Dark colors prefer upper case.
+fn mnemonics([r, g, b, _]: [u8; 4]) -> [&'static str; 2] {
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+    let lightness = (f32::from(max) + f32::from(min)) / 510.0;
+    let chroma = f32::from(max - min);
+    let channel = |c: u8| f32::from(c) / chroma;
+    let hue = if max == min {
+        0.0
+    } else if max == r {
+        60.0 * (channel(g) - channel(b)).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * (channel(b) - channel(r) + 2.0)
+    } else {
+        60.0 * (channel(r) - channel(g) + 4.0)
+    };
 
-    Ok(())
+    let (lower, upper) = match hue {
+        _ if chroma < 40.0 && lightness < 0.2 => return ["k", "K"],
+        _ if chroma < 40.0 && lightness > 0.85 => return ["w", "W"],
+        _ if chroma < 40.0 => ("s", "S"),
+        h if h < 15.0 => ("r", "R"),
+        h if h < 45.0 => ("o", "O"),
+        h if h < 70.0 => ("y", "Y"),
+        h if h < 165.0 => ("g", "G"),
+        h if h < 195.0 => ("c", "C"),
+        h if h < 255.0 => ("b", "B"),
+        h if h < 285.0 => ("v", "V"),
+        h if h < 345.0 => ("m", "M"),
+        _ => ("r", "R"),
+    };
+
+    if lightness < 0.35 {
+        [upper, lower]
+    } else {
+        [lower, upper]
+    }
+}
+
+/// The relative luminance of a color, scaled to an integer so it sorts.
+fn luminance([r, g, b, _]: [u8; 4]) -> u32 {
+    2126 * u32::from(r) + 7152 * u32::from(g) + 722 * u32::from(b)
+}
+
+/// `output` with `-<index>` appended to its name.
+fn flat_path(output: &str, index: u32) -> PathBuf {
+    let path = Path::new(output);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map_or("eno".into(), |e| e.to_string_lossy());
+    path.with_file_name(format!("{stem}-{index}.{extension}"))
+}
+
+impl Mabel {
+    /// Writes the document as an aseprite file, see [`Mabel::to_aseprite`].
+    pub fn save_aseprite(&self, path: &str) -> Result<()> {
+        std::fs::write(path, self.to_aseprite()?)?;
+
+        Ok(())
+    }
+
+    /// The document as an aseprite file at its unscaled size, with its layers
+    /// and their groups, matched across frames by name. The palette is in declaration order with
+    /// the keys as color names, and the file is indexed unless it has more
+    /// than 256 colors.
+    pub fn to_aseprite(&self) -> Result<Vec<u8>> {
+        let (width, height) = (self.width(), self.height());
+        let too_large = || format!("The image is too large for aseprite, found {width}x{height}");
+        let mut ase = AsepriteWriter::new(
+            u16::try_from(width).map_err(|_| too_large())?,
+            u16::try_from(height).map_err(|_| too_large())?,
+        );
+
+        // hidden and covered layers can have colors that are never drawn
+        let mut colors = self.indexed_colors().into_iter().collect::<IndexSet<_>>();
+        for layer in self.layers() {
+            colors.extend(layer.pixels.iter().flatten().map(Pixel::rgba));
+        }
+        ase.palette = colors
+            .iter()
+            .map(|rgba| NewColor {
+                rgba: *rgba,
+                name: self
+                    .palette
+                    .iter()
+                    .find(|(_, p)| matches!(p, Pixel::Colored(_)) && p.rgba() == *rgba)
+                    .map(|(key, _)| key.clone()),
+            })
+            .collect();
+        // the palette always has its transparent entry, unless it's empty
+        let transparent = colors
+            .iter()
+            .position(|c| *c == [0, 0, 0, 0])
+            .unwrap_or_else(|| {
+                ase.palette.push([0, 0, 0, 0].into());
+                colors.len()
+            });
+        if ase.palette.len() <= 256 {
+            ase.pixel_format = PixelFormat::Indexed {
+                transparent_color_index: transparent as u8,
+            };
+        }
+
+        // layers are matched across frames by their group and name
+        let mut indices = HashMap::new();
+        let mut groups: Vec<&str> = vec![];
+        for layer in self.layers() {
+            let id = (layer.group.as_deref(), layer.name.as_str());
+            if indices.contains_key(&id) {
+                continue;
+            }
+
+            // groups are written before their layers, closing the ones left
+            let path = layer
+                .group
+                .as_deref()
+                .map_or(vec![], |g| g.split('/').collect());
+            let shared = groups.iter().zip(&path).take_while(|(a, b)| a == b).count();
+            groups.truncate(shared);
+            for name in &path[shared..] {
+                let mut group = NewLayer::new(name);
+                group.is_group = true;
+                group.child_level = groups.len() as u16;
+                ase.layers.push(group);
+                groups.push(name);
+            }
+
+            let mut new_layer = NewLayer::new(&layer.name);
+            new_layer.child_level = groups.len() as u16;
+            new_layer.opacity = layer.opacity;
+            new_layer.blend_mode = layer.blend_mode;
+            new_layer.flags.set(LayerFlags::VISIBLE, layer.visible);
+            indices.insert(id, ase.layers.len());
+            ase.layers.push(new_layer);
+        }
+
+        for frame in &self.frames {
+            let duration = u16::try_from(self.frame_duration(frame)).unwrap_or(u16::MAX);
+            let mut new_frame = NewFrame::new(duration);
+            for layer in &frame.layers {
+                let index = indices[&(layer.group.as_deref(), layer.name.as_str())];
+                // a layer repeated within a frame keeps its first cel
+                if new_frame.cels.iter().any(|c| c.layer == index as u16) {
+                    continue;
+                }
+                new_frame.cels.push(NewCel::image(
+                    index as u16,
+                    image(&layer.pixels, width, height),
+                ));
+            }
+            ase.frames.push(new_frame);
+        }
+
+        let mut data = Vec::new();
+        ase.write(&mut data)?;
+
+        Ok(data)
+    }
+}
+
+/// Pixels unscaled and padded to the document size.
+fn image(pixels: &[Vec<Pixel>], width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        let pixel = pixels.get(y as usize).and_then(|l| l.get(x as usize));
+        Rgba(pixel.map_or([0, 0, 0, 0], Pixel::rgba))
+    })
 }
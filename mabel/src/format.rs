@@ -0,0 +1,128 @@
+// mabel, declarative pixel art
+// Copyright (c) 2024 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{path::Path, str::FromStr};
+
+#[cfg(any(feature = "qoi", feature = "bmp", feature = "tga", feature = "webp"))]
+use image::{ColorType, ImageEncoder};
+
+use crate::{Error, Mabel, Result};
+
+/// An output image format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Gif,
+    Svg,
+    Ico,
+    Cur,
+    #[cfg(feature = "qoi")]
+    Qoi,
+    #[cfg(feature = "bmp")]
+    Bmp,
+    #[cfg(feature = "tga")]
+    Tga,
+    /// Lossless WebP.
+    #[cfg(feature = "webp")]
+    WebP,
+}
+
+impl Format {
+    /// The format of a path by its extension, png when there's none.
+    pub fn from_path(path: &str) -> Result<Self> {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some(extension) => extension.to_ascii_lowercase().parse(),
+            None => Ok(Self::Png),
+        }
+    }
+
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Gif => "gif",
+            Self::Svg => "svg",
+            Self::Ico => "ico",
+            Self::Cur => "cur",
+            #[cfg(feature = "qoi")]
+            Self::Qoi => "qoi",
+            #[cfg(feature = "bmp")]
+            Self::Bmp => "bmp",
+            #[cfg(feature = "tga")]
+            Self::Tga => "tga",
+            #[cfg(feature = "webp")]
+            Self::WebP => "webp",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "png" | "apng" => Ok(Self::Png),
+            "gif" => Ok(Self::Gif),
+            "svg" => Ok(Self::Svg),
+            "ico" => Ok(Self::Ico),
+            "cur" => Ok(Self::Cur),
+            #[cfg(feature = "qoi")]
+            "qoi" => Ok(Self::Qoi),
+            #[cfg(feature = "bmp")]
+            "bmp" => Ok(Self::Bmp),
+            #[cfg(feature = "tga")]
+            "tga" => Ok(Self::Tga),
+            #[cfg(feature = "webp")]
+            "webp" => Ok(Self::WebP),
+            // formats whose feature is disabled fall through to here
+            _ if ["qoi", "bmp", "tga", "webp"].contains(&s) => Err(format!(
+                "The {s} format isn't enabled, mabel has to be built with the \"{s}\" feature"
+            )
+            .into()),
+            _ => Err(format!(
+                "Unknown format \"{s}\", must be png, gif, svg, ico, cur, qoi, bmp, tga or webp"
+            )
+            .into()),
+        }
+    }
+}
+
+impl Mabel {
+    /// Writes the image in `format`. Formats without animation are written
+    /// with the first frame.
+    pub fn save(&self, path: &str, format: Format) -> Result<()> {
+        #[cfg(any(feature = "qoi", feature = "bmp", feature = "tga", feature = "webp"))]
+        let writer = || -> Result<_> { Ok(std::io::BufWriter::new(std::fs::File::create(path)?)) };
+
+        match format {
+            Format::Png => self.save_png(path),
+            Format::Gif => self.save_gif(path),
+            Format::Svg => self.save_svg(path, false),
+            Format::Ico => self.save_ico(path),
+            Format::Cur => self.save_cur(path),
+            #[cfg(feature = "qoi")]
+            Format::Qoi => self.encode(image::codecs::qoi::QoiEncoder::new(writer()?)),
+            #[cfg(feature = "bmp")]
+            Format::Bmp => self.encode(image::codecs::bmp::BmpEncoder::new(&mut writer()?)),
+            #[cfg(feature = "tga")]
+            Format::Tga => self.encode(image::codecs::tga::TgaEncoder::new(writer()?)),
+            #[cfg(feature = "webp")]
+            Format::WebP => self.encode(image::codecs::webp::WebPEncoder::new_lossless(writer()?)),
+        }
+    }
+
+    /// Writes the first frame as rgba with one of the `image` encoders.
+    #[cfg(any(feature = "qoi", feature = "bmp", feature = "tga", feature = "webp"))]
+    fn encode(&self, encoder: impl ImageEncoder) -> Result<()> {
+        let frame = self.frames.first().ok_or("The document has no image")?;
+        encoder.write_image(
+            &self.frame_rgba(frame),
+            self.image_width(),
+            self.image_height(),
+            ColorType::Rgba8,
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+// mabel, declarative pixel art
+// Copyright (c) 2024 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use image::{imageops, Rgba, RgbaImage};
+
+use crate::{Mabel, Pixel, Result};
+
+/// The sizes embedded in icons and cursors, in pixels.
+pub const SIZES: [u32; 4] = [16, 32, 48, 256];
+
+impl Mabel {
+    /// Writes the first frame as an icon with a rendering for each of [`SIZES`].
+    pub fn save_ico(&self, path: &str) -> Result<()> {
+        Ok(std::fs::write(path, self.to_ico(false)?)?)
+    }
+
+    /// Writes the first frame as a cursor with a rendering for each of
+    /// [`SIZES`], the hotspot defaults to the top-left corner.
+    pub fn save_cur(&self, path: &str) -> Result<()> {
+        Ok(std::fs::write(path, self.to_ico(true)?)?)
+    }
+
+    /// The first frame as an `.ico` file, or as a `.cur` file with `cursor`.
+    ///
+    /// Every rendering is scaled with nearest-neighbour to fit its size and
+    /// centered. The largest is stored as a png and the rest as bitmaps, which
+    /// older readers expect.
+    pub fn to_ico(&self, cursor: bool) -> Result<Vec<u8>> {
+        let (width, height) = (self.width(), self.height());
+        if width == 0 || height == 0 {
+            return Err("Can't write an empty image as an icon".into());
+        }
+
+        let pixels = self.pixels();
+        let image = RgbaImage::from_fn(width, height, |x, y| {
+            let pixel = pixels.get(y as usize).and_then(|l| l.get(x as usize));
+            Rgba(pixel.map_or([0, 0, 0, 0], Pixel::rgba))
+        });
+
+        let mut entries = vec![];
+        for size in SIZES {
+            let scale = f64::from(size) / f64::from(width.max(height));
+            let (w, h) = (
+                ((f64::from(width) * scale).round() as u32).max(1),
+                ((f64::from(height) * scale).round() as u32).max(1),
+            );
+            let (left, top) = ((size - w) / 2, (size - h) / 2);
+
+            let mut icon = RgbaImage::new(size, size);
+            let scaled = imageops::resize(&image, w, h, imageops::FilterType::Nearest);
+            imageops::replace(&mut icon, &scaled, i64::from(left), i64::from(top));
+
+            // planes and bit count for icons, the hotspot for cursors
+            let (a, b) = if cursor {
+                let (x, y) = self.hotspot.unwrap_or((0, 0));
+                (
+                    (f64::from(x) * scale) as u32 + left,
+                    (f64::from(y) * scale) as u32 + top,
+                )
+            } else {
+                (1, 32)
+            };
+
+            let data = if size >= 256 {
+                png(&icon)?
+            } else {
+                bitmap(&icon)
+            };
+            entries.push((size, a as u16, b as u16, data));
+        }
+
+        let mut ico = vec![];
+        ico.extend_from_slice(&0u16.to_le_bytes());
+        ico.extend_from_slice(&(if cursor { 2u16 } else { 1 }).to_le_bytes());
+        ico.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+
+        let mut offset = 6 + 16 * entries.len() as u32;
+        for (size, a, b, data) in &entries {
+            // 256 is written as 0
+            ico.push(*size as u8);
+            ico.push(*size as u8);
+            ico.push(0); // no color palette
+            ico.push(0);
+            ico.extend_from_slice(&a.to_le_bytes());
+            ico.extend_from_slice(&b.to_le_bytes());
+            ico.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            ico.extend_from_slice(&offset.to_le_bytes());
+            offset += data.len() as u32;
+        }
+        for (.., data) in entries {
+            ico.extend(data);
+        }
+
+        Ok(ico)
+    }
+}
+
+/// Reads a `hotspot: x, y` value.
+pub(crate) fn parse_hotspot(s: &str) -> Result<(u32, u32)> {
+    let coordinates = s
+        .split(',')
+        .map(|c| c.trim().parse::<u32>())
+        .collect::<std::result::Result<Vec<_>, _>>();
+
+    match coordinates.as_deref() {
+        Ok([x, y]) => Ok((*x, *y)),
+        _ => Err(format!("Invalid hotspot \"{s}\", must be x, y").into()),
+    }
+}
+
+fn png(image: &RgbaImage) -> Result<Vec<u8>> {
+    let mut data = vec![];
+
+    let mut img = png::Encoder::new(&mut data, image.width(), image.height());
+    img.set_color(png::ColorType::Rgba);
+    img.set_depth(png::BitDepth::Eight);
+    let mut writer = img.write_header()?;
+    writer.write_image_data(image.as_raw())?;
+    writer.finish()?;
+
+    Ok(data)
+}
+
+/// A 32-bit bgra bitmap without its file header, followed by the 1-bit mask
+/// of transparent pixels. Both are stored bottom-up.
+fn bitmap(image: &RgbaImage) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    // mask rows are padded to 32 bits
+    let mask_stride = width.div_ceil(32) * 4;
+
+    let mut data = vec![];
+    data.extend_from_slice(&40u32.to_le_bytes());
+    data.extend_from_slice(&width.to_le_bytes());
+    // the height covers both the colors and the mask
+    data.extend_from_slice(&(height * 2).to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&32u16.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&(width * height * 4 + mask_stride * height).to_le_bytes());
+    data.extend_from_slice(&[0; 16]);
+
+    for row in image.rows().rev() {
+        for Rgba([r, g, b, a]) in row {
+            data.extend_from_slice(&[*b, *g, *r, *a]);
+        }
+    }
+
+    for row in image.rows().rev() {
+        let mut mask = vec![0u8; mask_stride as usize];
+        for (x, pixel) in row.enumerate() {
+            if pixel[3] == 0 {
+                mask[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        data.extend(mask);
+    }
+
+    data
+}
@@ -0,0 +1,213 @@
+// mabel, declarative pixel art
+// Copyright (c) 2024 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{collections::HashMap, fmt::Write};
+
+use image::RgbaImage;
+use indexmap::IndexSet;
+
+use crate::{
+    palette::{format_color, key, key_width, Notation},
+    Result,
+};
+
+/// Converts a png image into an eno document, see [`to_eno`].
+pub fn save_to_eno(png_path: &str, output_path: &str, max_colors: Option<usize>) -> Result<()> {
+    let image = read_png(png_path)?;
+    std::fs::write(output_path, to_eno(&image, max_colors)?)?;
+
+    Ok(())
+}
+
+/// Reads a png of any color type and bit depth as 8-bit rgba.
+pub fn read_png(path: &str) -> Result<RgbaImage> {
+    let mut decoder = png::Decoder::new(std::fs::File::open(path)?);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data)?;
+    data.truncate(info.buffer_size());
+
+    let data = match info.color_type {
+        png::ColorType::Rgba => data,
+        png::ColorType::Rgb => data
+            .chunks(3)
+            .flat_map(|c| [c[0], c[1], c[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => data
+            .chunks(2)
+            .flat_map(|c| [c[0], c[0], c[0], c[1]])
+            .collect(),
+        png::ColorType::Grayscale => data.iter().flat_map(|&c| [c, c, c, 255]).collect(),
+        // expanded into rgb or rgba by the transformations
+        png::ColorType::Indexed => unreachable!(),
+    };
+
+    RgbaImage::from_raw(info.width, info.height, data)
+        .ok_or_else(|| "Invalid png image data".into())
+}
+
+/// Writes an image as an eno document. The image is scaled down by the
+/// largest block size that every pixel fits, which becomes its `size`, and
+/// its colors are reduced to `max_colors` with a median cut when there are
+/// more. Fully transparent pixels are left empty.
+pub fn to_eno(image: &RgbaImage, max_colors: Option<usize>) -> Result<String> {
+    if max_colors == Some(0) {
+        return Err("Invalid max colors 0, must be at least 1".into());
+    }
+
+    let size = scale(image);
+    let (width, height) = (image.width() / size, image.height() / size);
+    let pixel = |x: u32, y: u32| {
+        let rgba = image.get_pixel(x * size, y * size).0;
+        (rgba[3] != 0).then_some(rgba)
+    };
+
+    // colors in order of first appearance
+    let mut colors = IndexSet::new();
+    let mut counts = HashMap::new();
+    for y in 0..height {
+        for x in 0..width {
+            if let Some(rgba) = pixel(x, y) {
+                colors.insert(rgba);
+                *counts.entry(rgba).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut replacements = HashMap::new();
+    if let Some(max) = max_colors.filter(|max| colors.len() > *max) {
+        let counted = colors.iter().map(|c| (*c, counts[c])).collect::<Vec<_>>();
+        replacements = median_cut(counted, max);
+        colors = colors.iter().map(|c| replacements[c]).collect();
+    }
+    let replace = |rgba: [u8; 4]| *replacements.get(&rgba).unwrap_or(&rgba);
+
+    let key_width = key_width(colors.len())?;
+    let keys = colors
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (*c, key(i, key_width)))
+        .collect::<HashMap<_, _>>();
+
+    let mut eno = format!("size: {size}\n");
+    if key_width > 1 {
+        let _ = writeln!(eno, "key_width: {key_width}");
+    }
+    if !colors.is_empty() {
+        eno.push_str("palette:\n");
+        for color in &colors {
+            let _ = writeln!(
+                eno,
+                "{} = {}",
+                keys[color],
+                format_color(*color, Notation::Hex)
+            );
+        }
+    }
+
+    eno.push_str("\n-- pixels\n");
+    for y in 0..height {
+        for x in 0..width {
+            match pixel(x, y) {
+                Some(rgba) => eno.push_str(&keys[&replace(rgba)]),
+                None => eno.push_str(&" ".repeat(key_width)),
+            }
+        }
+        eno.push('\n');
+    }
+    eno.push_str("-- pixels\n");
+
+    Ok(eno)
+}
+
+/// The largest block size the image is made of, at most 255 as that's the
+/// largest `size`.
+fn scale(image: &RgbaImage) -> u32 {
+    let (width, height) = image.dimensions();
+    let gcd = {
+        let (mut a, mut b) = (width, height);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+
+    (2..=gcd.min(255))
+        .rev()
+        .filter(|size| gcd % size == 0)
+        .find(|size| {
+            image
+                .enumerate_pixels()
+                .all(|(x, y, p)| p == image.get_pixel(x - x % size, y - y % size))
+        })
+        .unwrap_or(1)
+}
+
+/// Reduces weighted colors to at most `max` by repeatedly splitting the
+/// group with the widest channel at its median, mapping every color to the
+/// weighted average of its group.
+fn median_cut(colors: Vec<([u8; 4], usize)>, max: usize) -> HashMap<[u8; 4], [u8; 4]> {
+    let range = |group: &[([u8; 4], usize)], channel: usize| {
+        let values = group.iter().map(|(c, _)| c[channel]);
+        values.clone().max().unwrap_or(0) - values.min().unwrap_or(0)
+    };
+    let widest = |group: &[([u8; 4], usize)]| {
+        (0..4)
+            .map(|channel| (range(group, channel), channel))
+            .max()
+            .unwrap()
+    };
+
+    let mut groups = vec![colors];
+    while groups.len() < max {
+        let Some((i, channel)) = groups
+            .iter()
+            .enumerate()
+            .filter(|(_, g)| g.len() > 1)
+            .map(|(i, g)| (widest(g), i))
+            .max()
+            .map(|((_, channel), i)| (i, channel))
+        else {
+            break;
+        };
+
+        let mut group = groups.swap_remove(i);
+        group.sort_by_key(|(c, _)| c[channel]);
+        // split where half the pixels are on either side
+        let total = group.iter().map(|(_, n)| n).sum::<usize>();
+        let mut seen = 0;
+        let median = group
+            .iter()
+            .position(|(_, n)| {
+                seen += n;
+                seen * 2 >= total
+            })
+            .unwrap_or(0)
+            .min(group.len() - 2);
+
+        let rest = group.split_off(median + 1);
+        groups.push(group);
+        groups.push(rest);
+    }
+
+    let mut replacements = HashMap::new();
+    for group in groups {
+        let total = group.iter().map(|(_, n)| n).sum::<usize>();
+        let average = std::array::from_fn(|channel| {
+            let sum = group
+                .iter()
+                .map(|(c, n)| usize::from(c[channel]) * n)
+                .sum::<usize>();
+            ((sum + total / 2) / total) as u8
+        });
+
+        for (color, _) in group {
+            replacements.insert(color, average);
+        }
+    }
+
+    replacements
+}
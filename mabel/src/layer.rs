@@ -0,0 +1,123 @@
+// mabel, declarative pixel art
+// Copyright (c) 2024 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use color_art::Color;
+use image::Rgba;
+pub use mabel_aseprite::BlendMode;
+
+use crate::{Pixel, Result};
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub name: String,
+    pub pixels: Vec<Vec<Pixel>>,
+    /// The layer opacity, from 0 to 255.
+    pub opacity: u8,
+    pub visible: bool,
+    pub blend_mode: BlendMode,
+    /// The group the layer is in, as `outer/inner` for nested groups. Groups
+    /// aren't rendered, they only keep the structure of aseprite files.
+    pub group: Option<String>,
+}
+
+impl Layer {
+    pub fn new(name: impl Into<String>, pixels: Vec<Vec<Pixel>>) -> Self {
+        Self {
+            name: name.into(),
+            pixels,
+            opacity: 255,
+            visible: true,
+            blend_mode: BlendMode::Normal,
+            group: None,
+        }
+    }
+
+    pub fn pixels_height(&self) -> usize {
+        self.pixels.len()
+    }
+
+    pub fn pixels_width(&self) -> usize {
+        self.pixels.iter().map(Vec::len).max().unwrap_or(0)
+    }
+}
+
+/// Composites the visible layers bottom-to-top using their blend modes.
+pub fn composite(layers: &[Layer]) -> Vec<Vec<Pixel>> {
+    let visible = layers.iter().filter(|l| l.visible).collect::<Vec<_>>();
+
+    // a single plain layer doesn't need blending, keeping its colors untouched
+    if let [layer] = visible.as_slice() {
+        if layer.opacity == 255 && layer.blend_mode == BlendMode::Normal {
+            return layer.pixels.clone();
+        }
+    }
+
+    let height = visible.iter().map(|l| l.pixels_height()).max().unwrap_or(0);
+    let width = visible.iter().map(|l| l.pixels_width()).max().unwrap_or(0);
+
+    let mut pixels = vec![];
+    for y in 0..height {
+        let mut line = vec![];
+        for x in 0..width {
+            let mut color = Rgba([0, 0, 0, 0]);
+            for layer in &visible {
+                let Some(pixel) = layer.pixels.get(y).and_then(|l| l.get(x)) else {
+                    continue;
+                };
+
+                color = layer
+                    .blend_mode
+                    .blend(color, Rgba(pixel.rgba()), layer.opacity);
+            }
+
+            line.push(match color.0 {
+                [_, _, _, 0] => Pixel::Trans,
+                [r, g, b, a] => Pixel::Colored(Color::new(r, g, b, f64::from(a) / 255.0)),
+            });
+        }
+        pixels.push(line);
+    }
+
+    pixels
+}
+
+/// The blend modes by the names used in documents.
+const BLEND_MODES: [(&str, BlendMode); 19] = [
+    ("normal", BlendMode::Normal),
+    ("multiply", BlendMode::Multiply),
+    ("screen", BlendMode::Screen),
+    ("overlay", BlendMode::Overlay),
+    ("darken", BlendMode::Darken),
+    ("lighten", BlendMode::Lighten),
+    ("color-dodge", BlendMode::ColorDodge),
+    ("color-burn", BlendMode::ColorBurn),
+    ("hard-light", BlendMode::HardLight),
+    ("soft-light", BlendMode::SoftLight),
+    ("difference", BlendMode::Difference),
+    ("exclusion", BlendMode::Exclusion),
+    ("hue", BlendMode::Hue),
+    ("saturation", BlendMode::Saturation),
+    ("color", BlendMode::Color),
+    ("luminosity", BlendMode::Luminosity),
+    ("addition", BlendMode::Addition),
+    ("subtract", BlendMode::Subtract),
+    ("divide", BlendMode::Divide),
+];
+
+pub fn parse_blend_mode(name: &str) -> Result<BlendMode> {
+    BLEND_MODES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, mode)| *mode)
+        .ok_or_else(|| format!("Unknown blend mode \"{name}\"").into())
+}
+
+/// The name of a blend mode, as accepted by [`parse_blend_mode`].
+pub fn blend_mode_name(mode: BlendMode) -> &'static str {
+    BLEND_MODES
+        .iter()
+        .find(|(_, m)| *m == mode)
+        .map_or("normal", |(name, _)| name)
+}
@@ -3,17 +3,46 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use color_art::Color;
+use color_art::{Color, ColorSpace};
+use indexmap::{IndexMap, IndexSet};
+use mabel_eno::{prelude::*, Embed};
 use png::Encoder;
-use std::{collections::HashMap, vec};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    vec,
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 #[cfg(feature = "aseprite")]
 pub mod aseprite;
+pub mod format;
+pub mod ico;
+pub mod import;
+pub mod layer;
+pub mod mirror;
+pub mod palette;
+pub mod preview;
+pub mod sheet;
+pub mod stamp;
+pub mod svg;
+#[cfg(test)]
+mod tests;
+
+pub use layer::{BlendMode, Layer};
+pub use mirror::{Center, Mirror};
+pub use sheet::{Sheet, Sprite};
 
 pub type Error = Box<dyn std::error::Error>;
 pub type Result<T> = std::result::Result<T, Error>;
 
-pub type Palette = HashMap<char, Pixel>;
+/// The palette keys and their pixels, in declaration order.
+pub type Palette = IndexMap<String, Pixel>;
+
+/// The duration of a frame in milliseconds, unless the document specifies otherwise.
+pub const DEFAULT_DURATION: u32 = 100;
 
 #[derive(Debug, Clone, Copy)]
 pub enum Pixel {
@@ -21,13 +50,56 @@ pub enum Pixel {
     Colored(Color),
 }
 
+impl Pixel {
+    pub fn rgba(&self) -> [u8; 4] {
+        match self {
+            Self::Colored(c) => {
+                // `Color::alpha` rounds to two decimals, which loses alpha steps
+                let alpha = c.vec_of(ColorSpace::RGBA)[3];
+                [c.red(), c.green(), c.blue(), (alpha * 255.0).round() as u8]
+            }
+            Self::Trans => [0, 0, 0, 0],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// The layers of the frame, from bottom to top.
+    pub layers: Vec<Layer>,
+    /// The frame duration in milliseconds, falls back to the document duration.
+    pub duration: Option<u32>,
+}
+
+impl Frame {
+    pub const fn new(layers: Vec<Layer>, duration: Option<u32>) -> Self {
+        Self { layers, duration }
+    }
+
+    /// The composited pixels of all visible layers.
+    pub fn pixels(&self) -> Vec<Vec<Pixel>> {
+        layer::composite(&self.layers)
+    }
+}
+
 #[derive(Debug)]
 pub struct Mabel {
     pub size: u8,
     pub width: Option<u32>,
     pub height: Option<u32>,
     pub palette: Palette,
-    pub pixels: Vec<Vec<Pixel>>,
+    pub frames: Vec<Frame>,
+    /// The default frame duration in milliseconds.
+    pub duration: u32,
+    /// How many times an animation is played, 0 loops forever.
+    pub loops: u32,
+    /// The sprites packed into a sheet, in document order.
+    pub sprites: Vec<Sprite>,
+    /// The cursor hotspot in pixels from the top-left corner.
+    pub hotspot: Option<(u32, u32)>,
+    /// The other files the document was read from, such as stamps and
+    /// palette files.
+    pub dependencies: Vec<PathBuf>,
 }
 
 impl Mabel {
@@ -43,17 +115,45 @@ impl Mabel {
             width,
             height,
             palette,
-            pixels,
+            frames: vec![Frame::new(vec![Layer::new("Layer 1", pixels)], None)],
+            duration: DEFAULT_DURATION,
+            loops: 0,
+            sprites: vec![],
+            hotspot: None,
+            dependencies: vec![],
         }
     }
 
     pub fn from_file(path: &str) -> Result<Self> {
-        let file = std::fs::read_to_string(path)?;
+        let path = Path::new(path);
 
-        Self::from(&mabel_eno::parse(&file)?)
+        Self::read(path, &[std::fs::canonicalize(path)?], None)
     }
 
     pub fn from(eno: &mabel_eno::Document) -> Result<Self> {
+        Self::from_dir(eno, Path::new(""))
+    }
+
+    /// Reads a document whose relative paths are resolved from `dir`.
+    pub fn from_dir(eno: &mabel_eno::Document, dir: &Path) -> Result<Self> {
+        Self::parse(eno, dir, &[], None)
+    }
+
+    /// Reads the file at `path`, which is the last of `includes`. Keys also in
+    /// `host` take its colors, so stamps are drawn in the host palette.
+    pub(crate) fn read(path: &Path, includes: &[PathBuf], host: Option<&Palette>) -> Result<Self> {
+        let file = std::fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        Self::parse(&mabel_eno::parse(&file)?, dir, includes, host)
+    }
+
+    fn parse(
+        eno: &mabel_eno::Document,
+        dir: &Path,
+        includes: &[PathBuf],
+        host: Option<&Palette>,
+    ) -> Result<Self> {
         let size = eno
             .field("size")?
             .optional_value()?
@@ -70,40 +170,139 @@ impl Mabel {
             .optional_value()?
             .and_then(|s| s.parse::<u32>().ok());
 
-        let mut palette = eno
-            .field("palette")?
-            .attributes()?
-            .iter()
-            .map(|a| {
-                if a.key().len() != 1 {
-                    return Err(format!(
-                        "Invalid palette key \"{}\", must be a single character",
-                        a.key()
-                    )
-                    .into());
-                };
-
-                let k = a.key().chars().next().unwrap();
-                let v = Pixel::Colored(a.required_value()?);
-
-                Ok((k, v))
-            })
-            .collect::<Result<Palette>>()?;
-        palette.insert(' ', Pixel::Trans);
-
-        let mut pixels = vec![];
-        for line in eno.embed("pixels")?.required_value::<String>()?.lines() {
-            let mut line_pixels = vec![];
-            for c in line.chars() {
-                let p = palette
-                    .get(&c)
-                    .ok_or_else(|| format!("Unknown palette key \"{c}\""))?;
-                line_pixels.push(*p);
+        let duration = eno
+            .field("duration")?
+            .optional_value()?
+            .and_then(|s| s.parse::<u32>().ok())
+            .unwrap_or(DEFAULT_DURATION);
+
+        let loops = eno
+            .field("loop")?
+            .optional_value()?
+            .and_then(|s| s.parse::<u32>().ok())
+            .unwrap_or(0);
+
+        let key_width = eno
+            .field("key_width")?
+            .optional_value()?
+            .map_or(Ok(1), |s| match s.parse::<usize>() {
+                Ok(w @ 1..=3) => Ok(w),
+                _ => Err(format!("Invalid key_width \"{s}\", must be 1, 2 or 3")),
+            })?;
+
+        let mirror = eno
+            .field("mirror")?
+            .optional_value()?
+            .map(|s| s.parse::<Mirror>())
+            .transpose()?;
+
+        let center = eno
+            .field("mirror_center")?
+            .optional_value()?
+            .map(|s| s.parse::<Center>())
+            .transpose()?
+            .unwrap_or_default();
+
+        let hotspot = eno
+            .field("hotspot")?
+            .optional_value()?
+            .map(|s| ico::parse_hotspot(&s))
+            .transpose()?;
+
+        let dependencies = RefCell::new(vec![]);
+        if let Some(file) = eno.field("palette_file")?.optional_value()? {
+            dependencies.borrow_mut().push(dir.join(file));
+        }
+
+        let mut palette = palette::parse(eno, dir, key_width)?;
+        for (key, pixel) in &mut palette {
+            if let Some(color) = host.and_then(|h| h.get(key)) {
+                *pixel = *color;
+            }
+        }
+        let context = Context {
+            dir,
+            palette: &palette,
+            key_width,
+            includes,
+            dependencies: &dependencies,
+        };
+
+        // stamps are kept aside until the document's own layers are mirrored
+        let mut frames = vec![];
+        let mut stamps = vec![];
+        for section in sections(eno.elements(), "frame") {
+            let duration = section
+                .field("duration")?
+                .optional_value()?
+                .and_then(|s| s.parse::<u32>().ok());
+            let (layers, frame_stamps) = parse_layers(
+                section.elements(),
+                section.optional_embed("pixels")?,
+                &context,
+            )?;
+
+            frames.push(Frame::new(layers, duration));
+            stamps.push(frame_stamps);
+        }
+
+        let mut sprites = Vec::<Sprite>::new();
+        for section in sections(eno.elements(), "sprite") {
+            let sprite = sheet::parse(section, &context)?;
+            if sprites.iter().any(|s| s.name == sprite.name) {
+                return Err(format!("Duplicate sprite name \"{}\"", sprite.name).into());
+            }
+
+            sprites.push(sprite);
+        }
+
+        if frames.is_empty() {
+            // a sprite sheet doesn't need an image of its own
+            if sprites.is_empty()
+                || eno.optional_embed("pixels")?.is_some()
+                || sections(eno.elements(), "layer").next().is_some()
+                || sections(eno.elements(), "stamp").next().is_some()
+            {
+                let (layers, frame_stamps) =
+                    parse_layers(eno.elements(), eno.optional_embed("pixels")?, &context)?;
+                frames.push(Frame::new(layers, None));
+                stamps.push(frame_stamps);
+            }
+        } else if eno.optional_embed("pixels")?.is_some()
+            || sections(eno.elements(), "layer").next().is_some()
+        {
+            return Err("A document with frames can't have top-level pixels or layers".into());
+        } else {
+            // top-level stamps are drawn over every frame
+            let top_stamps = sections(eno.elements(), "stamp")
+                .map(|s| stamp::parse(s, &context))
+                .collect::<Result<Vec<_>>>()?;
+            for frame_stamps in &mut stamps {
+                frame_stamps.extend(top_stamps.iter().cloned());
             }
-            pixels.push(line_pixels);
         }
 
-        let mabel = Self::new(size, width, height, palette, pixels);
+        let mut mabel = Self {
+            size,
+            width,
+            height,
+            palette,
+            frames,
+            duration,
+            loops,
+            sprites,
+            hotspot,
+            dependencies: dependencies.into_inner(),
+        };
+        mabel.dependencies.sort();
+        mabel.dependencies.dedup();
+
+        if let Some(mirror) = mirror {
+            mabel.mirror(mirror, center);
+        }
+        for (frame, frame_stamps) in mabel.frames.iter_mut().zip(stamps) {
+            frame.layers.extend(frame_stamps);
+        }
 
         if mabel.is_over_width() {
             return Err("Horizontal pixels are more than the specified width".into());
@@ -113,24 +312,152 @@ impl Mabel {
             return Err("Vertical pixels are more than the specified height".into());
         }
 
+        if let Some((x, y)) = mabel.hotspot {
+            if x >= mabel.width() || y >= mabel.height() {
+                return Err(format!("Hotspot {x}, {y} is outside the image").into());
+            }
+        }
+
         Ok(mabel)
     }
 
+    /// Writes the image as a png, animated documents are written as an apng.
     pub fn save_png(&self, path: &str) -> Result<()> {
-        let palette = self.palette();
         let file = std::fs::File::create(path)?;
-        let w = std::io::BufWriter::new(file);
 
+        self.write_png(std::io::BufWriter::new(file), None)
+    }
+
+    /// The image encoded as a png, or an apng when animated.
+    pub fn png(&self) -> Result<Vec<u8>> {
+        let mut data = vec![];
+        self.write_png(&mut data, None)?;
+
+        Ok(data)
+    }
+
+    /// Writes the image as an indexed png whose palette is
+    /// [`Mabel::indexed_colors`], or as rgba when there are more than 256.
+    pub fn save_indexed_png(&self, path: &str) -> Result<()> {
+        let file = std::fs::File::create(path)?;
+        let colors = self.indexed_colors();
+
+        self.write_png(
+            std::io::BufWriter::new(file),
+            (colors.len() <= 256).then_some(colors),
+        )
+    }
+
+    fn write_png(&self, w: impl std::io::Write, colors: Option<Vec<[u8; 4]>>) -> Result<()> {
         let mut img = Encoder::new(w, self.image_width(), self.image_height());
-        img.set_color(png::ColorType::Rgba);
-        img.set_depth(png::BitDepth::Eight);
+        let mut indices = None;
+        if let Some(colors) = colors {
+            let depth = match colors.len() {
+                0..=2 => png::BitDepth::One,
+                3..=4 => png::BitDepth::Two,
+                5..=16 => png::BitDepth::Four,
+                _ => png::BitDepth::Eight,
+            };
+            img.set_color(png::ColorType::Indexed);
+            img.set_depth(depth);
+            img.set_palette(
+                colors
+                    .iter()
+                    .flat_map(|c| &c[..3])
+                    .copied()
+                    .collect::<Vec<_>>(),
+            );
+
+            // trailing opaque entries can be left out of the trns chunk
+            if let Some(last) = colors.iter().rposition(|c| c[3] != 255) {
+                img.set_trns(colors[..=last].iter().map(|c| c[3]).collect::<Vec<_>>());
+            }
+
+            let map = colors
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (*c, i as u8))
+                .collect::<HashMap<_, _>>();
+            indices = Some((map, depth as u8));
+        } else {
+            img.set_color(png::ColorType::Rgba);
+            img.set_depth(png::BitDepth::Eight);
+        }
+        if self.is_animated() {
+            img.set_animated(self.frames.len() as u32, self.loops)?;
+        }
 
         let mut writer = img.write_header()?;
-        writer.write_image_data(&palette)?;
+        for frame in &self.frames {
+            if self.is_animated() {
+                writer.set_frame_delay(
+                    u16::try_from(self.frame_duration(frame)).unwrap_or(u16::MAX),
+                    1000,
+                )?;
+            }
+            let data = self.frame_rgba(frame);
+            match &indices {
+                Some((map, bits)) => writer.write_image_data(&pack_indices(
+                    &data,
+                    map,
+                    *bits,
+                    self.image_width() as usize,
+                ))?,
+                None => writer.write_image_data(&data)?,
+            }
+        }
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    pub fn save_gif(&self, path: &str) -> Result<()> {
+        let (Ok(width), Ok(height)) = (
+            u16::try_from(self.image_width()),
+            u16::try_from(self.image_height()),
+        ) else {
+            return Err("Image is too large to be written as a gif".into());
+        };
+
+        let file = std::fs::File::create(path)?;
+        let w = std::io::BufWriter::new(file);
+
+        let mut encoder = gif::Encoder::new(w, width, height, &[])?;
+        // gifs count the plays after the first one
+        encoder.set_repeat(match self.loops {
+            0 => gif::Repeat::Infinite,
+            loops => gif::Repeat::Finite(u16::try_from(loops - 1).unwrap_or(u16::MAX)),
+        })?;
+
+        for frame in &self.frames {
+            let mut data = self.frame_rgba(frame);
+            let mut gif_frame = gif::Frame::from_rgba_speed(width, height, &mut data, 10);
+            // gif delays are in hundredths of a second
+            gif_frame.delay = u16::try_from(self.frame_duration(frame) / 10).unwrap_or(u16::MAX);
+            gif_frame.dispose = gif::DisposalMethod::Background;
+            encoder.write_frame(&gif_frame)?;
+        }
 
         Ok(())
     }
 
+    /// The composited pixels of the first frame.
+    pub fn pixels(&self) -> Vec<Vec<Pixel>> {
+        self.frames.first().map_or_else(Vec::new, Frame::pixels)
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+
+    pub fn layers(&self) -> impl Iterator<Item = &Layer> {
+        self.frames.iter().flat_map(|f| &f.layers)
+    }
+
+    pub fn frame_duration(&self, frame: &Frame) -> u32 {
+        frame.duration.unwrap_or(self.duration)
+    }
+
     pub fn height(&self) -> u32 {
         self.height.unwrap_or_else(|| self.pixels_height())
     }
@@ -140,11 +467,11 @@ impl Mabel {
     }
 
     pub fn pixels_height(&self) -> u32 {
-        self.pixels.len() as u32
+        self.layers().map(Layer::pixels_height).max().unwrap_or(0) as u32
     }
 
     pub fn pixels_width(&self) -> u32 {
-        self.pixels.iter().map(Vec::len).max().unwrap_or(0) as u32
+        self.layers().map(Layer::pixels_width).max().unwrap_or(0) as u32
     }
 
     pub fn image_height(&self) -> u32 {
@@ -165,57 +492,200 @@ impl Mabel {
             .map_or(false, |height| self.pixels_height() > height)
     }
 
+    /// The colors of an indexed image: the palette in declaration order,
+    /// followed by any other colors drawn, such as those of blended layers.
+    pub fn indexed_colors(&self) -> Vec<[u8; 4]> {
+        let mut colors = self
+            .palette
+            .values()
+            .map(Pixel::rgba)
+            .collect::<IndexSet<_>>();
+        for frame in &self.frames {
+            colors.extend(
+                self.frame_rgba(frame)
+                    .chunks_exact(4)
+                    .map(|c| [c[0], c[1], c[2], c[3]]),
+            );
+        }
+
+        colors.into_iter().collect()
+    }
+
+    /// The scaled rgba data of the first frame.
     pub fn palette(&self) -> Vec<u8> {
-        let mut palette = vec![];
-
-        for line in &self.pixels {
-            for _ in 0..self.size {
-                if line.is_empty() {
-                    for _ in 0..self.width() {
-                        for _ in 0..u32::from(self.size) {
-                            palette.push(0);
-                            palette.push(0);
-                            palette.push(0);
-                            palette.push(0);
-                        }
-                    }
-                    continue;
-                }
+        self.frames
+            .first()
+            .map_or_else(Vec::new, |f| self.frame_rgba(f))
+    }
 
-                for pixel in line {
-                    match pixel {
-                        Pixel::Colored(c) => {
-                            for _ in 0..u32::from(self.size) {
-                                palette.push(c.red());
-                                palette.push(c.green());
-                                palette.push(c.blue());
-                                palette.push((c.alpha() * 255.0) as u8);
-                            }
-                        }
-                        Pixel::Trans => {
-                            for _ in 0..u32::from(self.size) {
-                                palette.push(0);
-                                palette.push(0);
-                                palette.push(0);
-                                palette.push(0);
-                            }
-                        }
-                    }
+    /// The scaled rgba data of a frame, padded with transparency to the image size.
+    pub fn frame_rgba(&self, frame: &Frame) -> Vec<u8> {
+        let size = usize::from(self.size);
+        let width = self.width() as usize;
+        let mut data =
+            Vec::with_capacity(self.image_width() as usize * self.image_height() as usize * 4);
+
+        let pixels = frame.pixels();
+        for y in 0..self.height() as usize {
+            let line = pixels.get(y).map_or(&[][..], Vec::as_slice);
+            let mut row = Vec::with_capacity(width * size * 4);
+            for x in 0..width {
+                let rgba = line.get(x).map_or([0, 0, 0, 0], Pixel::rgba);
+                for _ in 0..size {
+                    row.extend_from_slice(&rgba);
                 }
+            }
 
-                if line.len() != self.width() as usize {
-                    for _ in 0..self.width() as usize - line.len() {
-                        for _ in 0..u32::from(self.size) {
-                            palette.push(0);
-                            palette.push(0);
-                            palette.push(0);
-                            palette.push(0);
-                        }
-                    }
-                }
+            for _ in 0..size {
+                data.extend_from_slice(&row);
+            }
+        }
+
+        data
+    }
+}
+
+/// Packs the palette indices of rgba `data` into rows of `bits` per pixel.
+fn pack_indices(data: &[u8], indices: &HashMap<[u8; 4], u8>, bits: u8, width: usize) -> Vec<u8> {
+    let per_byte = usize::from(8 / bits);
+
+    let mut packed = vec![];
+    for row in data.chunks(width.max(1) * 4) {
+        for pixels in row.chunks(per_byte * 4) {
+            let mut byte = 0;
+            for (i, rgba) in pixels.chunks_exact(4).enumerate() {
+                byte |= indices[rgba] << (8 - usize::from(bits) * (i + 1));
             }
+            packed.push(byte);
+        }
+    }
+
+    packed
+}
+
+fn sections<'a>(
+    elements: &'a [Box<dyn SectionElement>],
+    key: &'a str,
+) -> impl Iterator<Item = &'a mabel_eno::Section> {
+    elements
+        .iter()
+        .filter(move |e| e.key() == key)
+        .filter_map(|e| e.as_section())
+}
+
+/// The state shared while reading a document.
+pub(crate) struct Context<'a> {
+    /// The directory relative paths are resolved from.
+    pub dir: &'a Path,
+    pub palette: &'a Palette,
+    pub key_width: usize,
+    /// The canonical paths of the files being read, outermost first.
+    pub includes: &'a [PathBuf],
+    /// The other files read so far.
+    pub dependencies: &'a RefCell<Vec<PathBuf>>,
+}
+
+fn parse_layers(
+    elements: &[Box<dyn SectionElement>],
+    pixels: Option<&Embed>,
+    context: &Context,
+) -> Result<(Vec<Layer>, Vec<Layer>)> {
+    let Context {
+        palette, key_width, ..
+    } = *context;
+
+    let mut layers = vec![];
+    for (i, section) in sections(elements, "layer").enumerate() {
+        let name = section
+            .field("name")?
+            .optional_value()?
+            .unwrap_or_else(|| format!("Layer {}", i + 1));
+        let pixels = section.embed("pixels")?.required_value::<String>()?;
+
+        let mut layer = Layer::new(name, parse_pixels(&pixels, palette, key_width)?);
+        layer.opacity = section
+            .field("opacity")?
+            .optional_value()?
+            .and_then(|s| s.parse::<u8>().ok())
+            .unwrap_or(255);
+        layer.visible = section
+            .field("visible")?
+            .optional_value()?
+            .and_then(|s| s.parse::<bool>().ok())
+            .unwrap_or(true);
+        if let Some(blend) = section.field("blend")?.optional_value()? {
+            layer.blend_mode = layer::parse_blend_mode(&blend)?;
+        }
+        layer.group = section.field("group")?.optional_value()?;
+
+        layers.push(layer);
+    }
+
+    let stamps = sections(elements, "stamp")
+        .map(|s| stamp::parse(s, context))
+        .collect::<Result<Vec<_>>>()?;
+
+    match (pixels, layers.is_empty()) {
+        (Some(pixels), true) => {
+            let pixels = pixels.required_value::<String>()?;
+            layers.push(Layer::new(
+                "Layer 1",
+                parse_pixels(&pixels, palette, key_width)?,
+            ));
         }
+        (None, true) if stamps.is_empty() => return Err("Missing pixels, layers or stamps".into()),
+        (Some(_), false) => return Err("Pixels can't be used alongside layers".into()),
+        _ => {}
+    }
+
+    Ok((layers, stamps))
+}
+
+/// Reads the pixels grid in cells of `key_width` grapheme clusters.
+///
+/// Every cell spans as many display columns as the widest palette key, so
+/// narrower keys are followed by spaces to fill it, except at the end of a
+/// line. The padding is always there, which keeps it apart from transparent
+/// cells.
+fn parse_pixels(embed: &str, palette: &Palette, key_width: usize) -> Result<Vec<Vec<Pixel>>> {
+    let cell_width = palette.keys().map(|k| k.width()).max().unwrap_or(1);
+
+    let mut pixels = vec![];
+    for (i, line) in embed.lines().enumerate() {
+        let graphemes = line.graphemes(true).collect::<Vec<_>>();
+        let mut rest = graphemes.as_slice();
+
+        let mut line_pixels = vec![];
+        while !rest.is_empty() {
+            if rest.len() < key_width {
+                return Err(format!(
+                    "Pixels line {} is {} characters long, which is not a multiple of the key width {key_width}",
+                    i + 1,
+                    graphemes.len()
+                )
+                .into());
+            }
 
-        palette
+            let (cell, tail) = rest.split_at(key_width);
+            let key = cell.concat();
+            let p = palette
+                .get(&key)
+                .ok_or_else(|| format!("Unknown palette key \"{key}\""))?;
+            line_pixels.push(*p);
+
+            let padding = cell_width.saturating_sub(key.width());
+            let spaces = tail.iter().take(padding).take_while(|g| **g == " ").count();
+            if spaces < padding && spaces < tail.len() {
+                return Err(format!(
+                    "Pixels line {} needs padding after \"{key}\" to line up with the widest palette key",
+                    i + 1
+                )
+                .into());
+            }
+            rest = &tail[spaces..];
+        }
+        pixels.push(line_pixels);
     }
+
+    Ok(pixels)
 }
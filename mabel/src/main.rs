@@ -3,13 +3,24 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use std::path::Path;
+
 use clap::{
     builder::{styling::AnsiColor, Styles},
-    Parser, Args
+    Parser, Args, Subcommand
 };
 #[cfg(feature = "aseprite")]
-use clap::Subcommand;
-use mabel::Mabel;
+use mabel::aseprite::{ConvertOptions, Frames, KeyOptions, PixelSize};
+use mabel::{
+    palette::{key, presets::PRESETS},
+    preview::Protocol,
+    format::Format,
+    sheet::SheetOptions,
+    Error, Mabel, Result,
+};
+
+mod serve;
+mod watch;
 
 const fn clap_style() -> Styles {
     Styles::styled()
@@ -23,7 +34,6 @@ const fn clap_style() -> Styles {
 #[derive(Parser)]
 #[clap(version, author, styles = clap_style())]
 struct MabelArgs {
-    #[cfg(feature = "aseprite")]
     #[command(subcommand)]
     command: Option<Command>,
 
@@ -34,13 +44,59 @@ struct MabelArgs {
     /// The path to the output file
     #[arg(short, long)]
     output: Option<String>,
+
+    /// The output format, inferred from the output extension by default:
+    /// png, gif, svg, ico, cur, qoi, bmp, tga or webp
+    #[arg(short, long)]
+    format: Option<String>,
+
+    /// Keep running and render again whenever the file, or a file it depends
+    /// on, changes. A directory renders every .eno file in it into the output
+    /// directory
+    #[arg(short, long)]
+    watch: bool,
+
+    /// Write the png with an indexed palette in declaration order
+    #[arg(long)]
+    indexed: bool,
+
+    /// Group svg rectangles by palette key, with a class for each key
+    #[arg(long)]
+    classes: bool,
+
+    /// The path to the sprite sheet data file, either .json or .eno
+    #[arg(long)]
+    data: Option<String>,
+
+    /// How sprites are laid out in the sheet: rows, columns or packed
+    #[arg(long, default_value = "packed")]
+    packing: String,
+
+    /// The space between sprites in the sheet, in pixels
+    #[arg(long, default_value_t = 0)]
+    padding: u32,
+
+    /// Repeat the edges of every sprite one pixel outwards
+    #[arg(long)]
+    extrude: bool,
 }
 
-#[cfg(feature = "aseprite")]
 #[derive(Subcommand)]
 enum Command {
     /// Convert an aseprite file to eno
+    #[cfg(feature = "aseprite")]
     Aseprite(Aseprite),
+    /// Convert a mabel file to aseprite
+    #[cfg(feature = "aseprite")]
+    ExportAse(ExportAse),
+    /// Convert a png image to eno
+    Import(Import),
+    /// List the built-in palette presets
+    Palettes,
+    /// Draw a mabel file in the terminal
+    Preview(Preview),
+    /// Serve a directory of mabel files on localhost, reloading on changes
+    Serve(Serve),
 }
 
 #[derive(Args)]
@@ -53,34 +109,276 @@ struct Aseprite {
     /// The path to the output file
     #[arg(short, long)]
     output: Option<String>,
+
+    /// The frames to convert: all, a frame index or a first-last range
+    #[arg(long, default_value = "all")]
+    frames: String,
+
+    /// Convert the frames of the tag with this name
+    #[arg(long, conflicts_with = "frames")]
+    tag: Option<String>,
+
+    /// Write every frame to its own file, with the frame index appended to
+    /// the output name
+    #[arg(long)]
+    flat: bool,
+
+    /// Keep every layer as its own section instead of drawing them together
+    #[arg(long)]
+    layers: bool,
+
+    /// Draw opaque pixels over the layers below them, ignoring blend modes
+    /// and opacity
+    #[arg(long, conflicts_with = "layers")]
+    raw_layers: bool,
+
+    /// The order of the palette: palette, appearance or luminance
+    #[arg(long, default_value = "palette")]
+    order: String,
+
+    /// Pick keys that describe the colors, like r for red and k for black
+    #[arg(long)]
+    smart_keys: bool,
+
+    /// The characters palette keys are made of
+    #[arg(long)]
+    keys: Option<String>,
+
+    /// The size of each pixel
+    #[arg(long, default_value_t = 32)]
+    size: u8,
+
+    /// Pick the largest pixel size that keeps the image at most this wide
+    #[arg(long, conflicts_with = "size")]
+    target_width: Option<u32>,
+
+    /// How colors are written: hex, rgb, hsl or named
+    #[arg(long, default_value = "hex")]
+    notation: String,
+
+    /// Write the width and height of the image
+    #[arg(long)]
+    dimensions: bool,
+
+    /// Crop the transparent borders shared by every frame
+    #[arg(long)]
+    trim: bool,
+
+    /// Print the document instead of writing it to a file
+    #[arg(long, conflicts_with_all = ["output", "flat"])]
+    stdout: bool,
+}
+
+#[derive(Args)]
+struct ExportAse {
+    /// The path to the mabel file
+    #[arg()]
+    file: String,
+
+    /// The path to the output file
+    #[arg(short, long)]
+    output: Option<String>,
+}
+
+#[derive(Args)]
+struct Import {
+    /// The path to the png file
+    #[arg()]
+    file: String,
+
+    /// The path to the output file
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Reduce the image to at most this many colors
+    #[arg(long)]
+    max_colors: Option<usize>,
+}
+
+#[derive(Args)]
+struct Preview {
+    /// The path to the mabel file
+    #[arg()]
+    file: String,
+
+    /// How the image is drawn: truecolor, 256, sixel or kitty. Half blocks in
+    /// truecolor or 256 colors depending on the terminal by default
+    #[arg(short, long)]
+    protocol: Option<String>,
 }
 
-fn run() -> Result<(), Box<dyn std::error::Error>> {
+#[derive(Args)]
+struct Serve {
+    /// The directory of mabel files
+    #[arg(default_value = ".")]
+    dir: String,
+
+    /// The port to listen on
+    #[arg(short, long, default_value_t = 8080)]
+    port: u16,
+}
+
+fn run() -> Result<()> {
     let args = MabelArgs::parse();
 
-    #[cfg(feature = "aseprite")]
-    if let Some(Command::Aseprite(args)) = args.command {
-        let output = args.output.unwrap_or("output.eno".to_owned());
-        mabel::aseprite::save_to_eno(&args.file, &output)?;
+    match args.command {
+        #[cfg(feature = "aseprite")]
+        Some(Command::Aseprite(args)) => {
+            let options = ConvertOptions {
+                frames: match args.tag {
+                    Some(tag) => Frames::Tag(tag),
+                    None => args.frames.parse()?,
+                },
+                flat: args.flat,
+                layers: args.layers,
+                raw_layers: args.raw_layers,
+                keys: KeyOptions {
+                    order: args.order.parse()?,
+                    smart: args.smart_keys,
+                    alphabet: args.keys,
+                },
+                size: match args.target_width {
+                    Some(width) => PixelSize::Width(width),
+                    None => PixelSize::Fixed(args.size),
+                },
+                notation: args.notation.parse()?,
+                dimensions: args.dimensions,
+                trim: args.trim,
+            };
 
-        return Ok(());
+            if args.stdout {
+                let ase = mabel_aseprite::AsepriteFile::read_file(Path::new(&args.file))?;
+                print!("{}", mabel::aseprite::to_eno(&ase, &options)?.concat());
+            } else {
+                let output = args.output.unwrap_or("output.eno".to_owned());
+                mabel::aseprite::save_to_eno(&args.file, &output, &options)?;
+            }
+
+            return Ok(());
+        }
+        #[cfg(feature = "aseprite")]
+        Some(Command::ExportAse(args)) => {
+            let output = args.output.unwrap_or("output.aseprite".to_owned());
+            Mabel::from_file(&args.file)?.save_aseprite(&output)?;
+
+            return Ok(());
+        }
+        Some(Command::Import(args)) => {
+            let output = args.output.unwrap_or("output.eno".to_owned());
+            mabel::import::save_to_eno(&args.file, &output, args.max_colors)?;
+
+            return Ok(());
+        }
+        Some(Command::Palettes) => {
+            print_palettes();
+
+            return Ok(());
+        }
+        Some(Command::Preview(args)) => {
+            let protocol = match args.protocol {
+                Some(protocol) => protocol.parse()?,
+                None => Protocol::detect(),
+            };
+            print!("{}", Mabel::from_file(&args.file)?.preview(protocol)?);
+
+            return Ok(());
+        }
+        Some(Command::Serve(args)) => return serve::serve(&args.dir, args.port),
+        None => {}
     }
 
-    let output = args.output.unwrap_or("output.png".to_owned());
-    let mabel = if let Some(file) = args.file {
-        Mabel::from_file(&file)?
-    } else {
+    let Some(file) = args.file.clone() else {
         return Err("No eno file provided.".into());
     };
+    let format = args.format.as_deref().map(str::parse).transpose()?;
+
+    if args.watch {
+        return watch::watch(&file, format, &args);
+    }
+
+    let output = args.output.clone().unwrap_or_else(|| {
+        format!("output.{}", format.unwrap_or(Format::Png).extension())
+    });
+    let format = match format {
+        Some(format) => format,
+        None => Format::from_path(&output)?,
+    };
+
+    render(
+        &Mabel::from_file(&file)?,
+        &output,
+        format,
+        args.data.as_deref(),
+        &args,
+    )
+}
+
+/// Writes the document to `output`, sprite sheets are written along with
+/// their `data` file when there's one.
+fn render(
+    mabel: &Mabel,
+    output: &str,
+    format: Format,
+    data: Option<&str>,
+    args: &MabelArgs,
+) -> Result<()> {
+    if !mabel.sprites.is_empty() {
+        if format != Format::Png {
+            return Err("Sprite sheets can only be written as a png".into());
+        }
 
-    mabel.save_png(&output)?;
+        let sheet = mabel.sheet(SheetOptions {
+            packing: args.packing.parse()?,
+            padding: args.padding,
+            extrude: args.extrude,
+        })?;
+        sheet.save_png(output)?;
+
+        if let Some(data) = data {
+            let image = Path::new(output)
+                .file_name()
+                .map_or(output.to_owned(), |f| f.to_string_lossy().into_owned());
+            sheet.save_data(data, &image)?;
+        }
+
+        return Ok(());
+    }
+
+    match format {
+        Format::Png if args.indexed => mabel.save_indexed_png(output),
+        Format::Svg => mabel.save_svg(output, args.classes),
+        format => mabel.save(output, format),
+    }
+}
+
+fn print_palettes() {
+    for preset in PRESETS {
+        println!(
+            "\x1b[1m{}\x1b[0m {} ({} colors)",
+            preset.name,
+            preset.title,
+            preset.colors.len()
+        );
+
+        for (i, color) in preset.colors().iter().enumerate() {
+            let (r, g, b) = (color.red(), color.green(), color.blue());
+            let fg = if color.luminance() > 0.4 { 0 } else { 255 };
+            print!(
+                "\x1b[48;2;{r};{g};{b}m\x1b[38;2;{fg};{fg};{fg}m {} \x1b[0m",
+                key(i, 1)
+            );
+        }
+        println!("\n");
+    }
+}
 
-    Ok(())
+fn print_error(e: &Error) {
+    eprintln!("\x1b[1;31merror\x1b[0;1m: {e}\x1b[0m");
 }
 
 fn main() {
     if let Err(e) = run() {
-        eprintln!("\x1b[1;31merror\x1b[0;1m: {e}");
+        print_error(&e);
         std::process::exit(1);
     }
 }
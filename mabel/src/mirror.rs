@@ -0,0 +1,85 @@
+// mabel, declarative pixel art
+// Copyright (c) 2024 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::str::FromStr;
+
+use crate::{Error, Mabel, Pixel};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirror {
+    /// The left half is drawn and mirrored to the right.
+    Horizontal,
+    /// The top half is drawn and mirrored to the bottom.
+    Vertical,
+    /// The top-left quarter is drawn and mirrored to the other three.
+    Both,
+}
+
+impl FromStr for Mirror {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "horizontal" => Ok(Self::Horizontal),
+            "vertical" => Ok(Self::Vertical),
+            "both" => Ok(Self::Both),
+            _ => {
+                Err(format!("Invalid mirror \"{s}\", must be horizontal, vertical or both").into())
+            }
+        }
+    }
+}
+
+/// Whether the last drawn row or column is the center of the mirrored image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Center {
+    /// The last row or column is repeated, making the size even.
+    #[default]
+    Even,
+    /// The last row or column is shared, making the size odd.
+    Odd,
+}
+
+impl FromStr for Center {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "even" => Ok(Self::Even),
+            "odd" => Ok(Self::Odd),
+            _ => Err(format!("Invalid mirror_center \"{s}\", must be even or odd").into()),
+        }
+    }
+}
+
+impl Mabel {
+    /// Expands the drawn half or quarter of every layer into the whole image.
+    ///
+    /// Documents are mirrored before their stamps are drawn, so stamps keep
+    /// their place and aren't mirrored.
+    pub fn mirror(&mut self, mirror: Mirror, center: Center) {
+        let width = self.pixels_width() as usize;
+        let height = self.pixels_height() as usize;
+        let skip = usize::from(center == Center::Odd);
+
+        for layer in self.frames.iter_mut().flat_map(|f| &mut f.layers) {
+            let pixels = &mut layer.pixels;
+
+            if mirror != Mirror::Vertical {
+                for line in pixels.iter_mut() {
+                    line.resize(width, Pixel::Trans);
+                    let mirrored = line.iter().rev().skip(skip).copied().collect::<Vec<_>>();
+                    line.extend(mirrored);
+                }
+            }
+
+            if mirror != Mirror::Horizontal {
+                pixels.resize(height, vec![]);
+                let mirrored = pixels.iter().rev().skip(skip).cloned().collect::<Vec<_>>();
+                pixels.extend(mirrored);
+            }
+        }
+    }
+}
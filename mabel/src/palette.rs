@@ -0,0 +1,393 @@
+// mabel, declarative pixel art
+// Copyright (c) 2024 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{path::Path, str::FromStr};
+
+use color_art::Color;
+use mabel_eno::{Attribute, Document};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{Error, Palette, Pixel, Result};
+
+pub mod presets;
+
+/// The characters used for generated palette keys, in order.
+pub const KEYS: &str = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// Reads the document palette, merging the inline `palette` entries with the
+/// colors of `palette_file`, which is resolved relative to `dir`, on top of
+/// the `palette_preset` colors.
+pub(crate) fn parse(eno: &Document, dir: &Path, key_width: usize) -> Result<Palette> {
+    let mut palette = Palette::new();
+    if let Some(field) = eno.optional_field("palette_preset")? {
+        let name = field.required_value::<String>()?;
+        let preset = presets::find(&name).ok_or_else(|| {
+            mabel_eno::Error::new(
+                format!(
+                    "Unknown palette preset \"{name}\" on line {}",
+                    field.line_number
+                ),
+                field.line_number,
+            )
+        })?;
+
+        for (i, color) in preset.colors().into_iter().enumerate() {
+            palette.insert(key(i, key_width), Pixel::Colored(color));
+        }
+    }
+
+    let imported = match eno.optional_field("palette_file")? {
+        Some(field) => {
+            let path = dir.join(field.required_value::<String>()?);
+            let colors = read_file(&path).map_err(|e| {
+                mabel_eno::Error::new(
+                    format!(
+                        "Invalid palette file \"{}\" on line {}: {e}",
+                        path.display(),
+                        field.line_number
+                    ),
+                    field.line_number,
+                )
+            })?;
+
+            Some(colors)
+        }
+        None => None,
+    };
+
+    let inline = parse_entries(
+        &eno.field("palette")?.attributes()?,
+        key_width,
+        imported.as_deref(),
+    )?;
+    palette.extend(inline);
+    palette.insert(" ".repeat(key_width), Pixel::Trans);
+
+    Ok(palette)
+}
+
+/// Reads `key: color` palette entries, integer values index into `imported`
+/// when there is one.
+pub(crate) fn parse_entries(
+    attributes: &[Attribute],
+    key_width: usize,
+    imported: Option<&[Color]>,
+) -> Result<Palette> {
+    attributes
+        .iter()
+        .map(|a| {
+            if a.key().graphemes(true).count() != key_width {
+                return Err(if key_width == 1 {
+                    format!(
+                        "Invalid palette key \"{}\", must be a single character",
+                        a.key()
+                    )
+                } else {
+                    format!(
+                        "Invalid palette key \"{}\", must be {key_width} characters long",
+                        a.key()
+                    )
+                }
+                .into());
+            };
+
+            let k = a.key().to_owned();
+            let v = match (imported, a.required_value::<String>()?.parse::<usize>()) {
+                (Some(colors), Ok(index)) => {
+                    let color = colors.get(index).ok_or_else(|| {
+                        mabel_eno::Error::new(
+                            format!(
+                                "Unknown palette index {index} on line {}, the palette file has {} colors",
+                                a.line_number,
+                                colors.len()
+                            ),
+                            a.line_number,
+                        )
+                    })?;
+
+                    Pixel::Colored(*color)
+                }
+                _ => Pixel::Colored(a.required_value()?),
+            };
+
+            Ok((k, v))
+        })
+        .collect()
+}
+
+/// Reads the colors of a GIMP (`.gpl`), Lospec (`.hex`), JASC (`.pal`) or
+/// Photoshop (`.aco`) palette file, in order.
+pub fn read_file(path: &Path) -> Result<Vec<Color>> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+
+    match extension.as_deref() {
+        Some("gpl") => read_gpl(&std::fs::read_to_string(path)?),
+        Some("hex") => read_hex(&std::fs::read_to_string(path)?),
+        Some("pal") => read_pal(&std::fs::read_to_string(path)?),
+        Some("aco") => read_aco(&std::fs::read(path)?),
+        _ => Err("Unsupported palette format, expected a .gpl, .hex, .pal or .aco file".into()),
+    }
+}
+
+pub fn read_gpl(file: &str) -> Result<Vec<Color>> {
+    let mut lines = file.lines().enumerate();
+    if lines.next().map(|(_, l)| l.trim()) != Some("GIMP Palette") {
+        return Err("Missing \"GIMP Palette\" header".into());
+    }
+
+    let mut colors = vec![];
+    for (i, line) in lines {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("Name:")
+            || line.starts_with("Columns:")
+        {
+            continue;
+        }
+
+        colors.push(rgb(line.split_whitespace(), i + 1)?);
+    }
+
+    Ok(colors)
+}
+
+pub fn read_hex(file: &str) -> Result<Vec<Color>> {
+    file.lines()
+        .enumerate()
+        .map(|(i, l)| (i, l.trim()))
+        .filter(|(_, l)| !l.is_empty())
+        .map(|(i, l)| {
+            let hex = l.strip_prefix('#').unwrap_or(l);
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("Invalid color \"{l}\" on line {}", i + 1).into());
+            }
+
+            Ok(Color::from_hex(&format!("#{hex}"))?)
+        })
+        .collect()
+}
+
+pub fn read_pal(file: &str) -> Result<Vec<Color>> {
+    let mut lines = file.lines().map(str::trim).enumerate();
+    if lines.next().map(|(_, l)| l) != Some("JASC-PAL") {
+        return Err("Missing \"JASC-PAL\" header".into());
+    }
+    lines.next().ok_or("Missing palette version")?;
+
+    let count = lines
+        .next()
+        .and_then(|(_, l)| l.parse::<usize>().ok())
+        .ok_or("Missing palette color count")?;
+
+    let colors = lines
+        .filter(|(_, l)| !l.is_empty())
+        .map(|(i, l)| rgb(l.split_whitespace(), i + 1))
+        .collect::<Result<Vec<_>>>()?;
+
+    if colors.len() != count {
+        return Err(format!("Expected {count} colors, found {}", colors.len()).into());
+    }
+
+    Ok(colors)
+}
+
+pub fn read_aco(file: &[u8]) -> Result<Vec<Color>> {
+    let mut words = file.chunks(2).map(|w| w.try_into().map(u16::from_be_bytes));
+    let mut word = || -> Result<u16> {
+        Ok(words
+            .next()
+            .ok_or("Unexpected end of file")?
+            .map_err(|_| "Unexpected end of file")?)
+    };
+
+    let version = word()?;
+    if version != 1 && version != 2 {
+        return Err(format!("Unsupported aco version {version}").into());
+    }
+
+    let count = word()?;
+    let mut colors = vec![];
+    for _ in 0..count {
+        let space = word()?;
+        let [w, x, y, z] = [word()?, word()?, word()?, word()?];
+        let (w, x, y) = (f64::from(w), f64::from(x), f64::from(y));
+
+        let color = match space {
+            // rgb, 0..=65535 per channel
+            0 => Color::from_rgb(
+                (w / 257.0).round(),
+                (x / 257.0).round(),
+                (y / 257.0).round(),
+            )?,
+            // hsb, hue in 0..=65535 and saturation/brightness in 0..=65535
+            1 => Color::from_hsv(w / 65535.0 * 360.0, x / 65535.0, y / 65535.0)?,
+            // cmyk, 0 is 100% ink
+            2 => Color::from_cmyk(
+                1.0 - w / 65535.0,
+                1.0 - x / 65535.0,
+                1.0 - y / 65535.0,
+                1.0 - f64::from(z) / 65535.0,
+            )?,
+            // grayscale, 0..=10000 where 10000 is black
+            8 => {
+                let gray = (255.0 - w / 10000.0 * 255.0).round();
+                Color::from_rgb(gray, gray, gray)?
+            }
+            _ => return Err(format!("Unsupported aco color space {space}").into()),
+        };
+
+        if version == 2 {
+            // the utf-16 name length in code units, followed by the name itself
+            let name = (u32::from(word()?) << 16) | u32::from(word()?);
+            for _ in 0..name {
+                word()?;
+            }
+        }
+
+        colors.push(color);
+    }
+
+    Ok(colors)
+}
+
+fn rgb<'a>(mut values: impl Iterator<Item = &'a str>, line: usize) -> Result<Color> {
+    let mut channel = || {
+        values
+            .next()
+            .and_then(|v| v.parse::<u8>().ok())
+            .ok_or_else(|| format!("Invalid color on line {line}"))
+    };
+
+    Ok(Color::from_rgb(channel()?, channel()?, channel()?)?)
+}
+
+/// The smallest key width that fits `colors` unique keys.
+pub fn key_width(colors: usize) -> Result<usize> {
+    key_width_with(colors, KEYS.len())
+}
+
+/// The smallest key width that fits `colors` unique keys made of `letters`
+/// different characters.
+pub fn key_width_with(colors: usize, letters: usize) -> Result<usize> {
+    (1..=3)
+        .find(|width| letters.checked_pow(*width).unwrap_or(usize::MAX) >= colors)
+        .map(|width| width as usize)
+        .ok_or_else(|| format!("Too many colors for palette keys, found {colors} colors").into())
+}
+
+/// The key for the palette entry at `index`, `width` characters long.
+pub fn key(mut index: usize, width: usize) -> String {
+    let mut key = vec![0; width];
+    for c in key.iter_mut().rev() {
+        *c = KEYS.as_bytes()[index % KEYS.len()];
+        index /= KEYS.len();
+    }
+
+    String::from_utf8(key).unwrap()
+}
+
+/// The key for the palette entry at `index`, `width` letters of `alphabet` long.
+pub fn key_with(mut index: usize, width: usize, alphabet: &[&str]) -> String {
+    let mut key = vec![""; width];
+    for c in key.iter_mut().rev() {
+        *c = alphabet[index % alphabet.len()];
+        index /= alphabet.len();
+    }
+
+    key.concat()
+}
+
+/// How colors are written in generated documents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Notation {
+    /// `#rrggbb`.
+    #[default]
+    Hex,
+    /// `rgb(r, g, b)`.
+    Rgb,
+    /// `hsl(h, s%, l%)`.
+    Hsl,
+    /// Color names like `red` where there's one, hex otherwise.
+    Named,
+}
+
+impl FromStr for Notation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "hex" => Ok(Self::Hex),
+            "rgb" => Ok(Self::Rgb),
+            "hsl" => Ok(Self::Hsl),
+            "named" => Ok(Self::Named),
+            _ => Err(format!("Invalid notation \"{s}\", must be hex, rgb, hsl or named").into()),
+        }
+    }
+}
+
+/// A color in `notation`. Translucent colors are written as `rgba(...)`, or
+/// `hsla(...)` in hsl, as hex and names can't hold their alpha.
+pub fn format_color(rgba: [u8; 4], notation: Notation) -> String {
+    let [r, g, b, a] = rgba;
+    // three decimals tell every alpha step apart
+    let alpha = format!("{:.3}", f32::from(a) / 255.0);
+    let alpha = alpha.trim_end_matches('0').trim_end_matches('.');
+    match notation {
+        Notation::Hsl => {
+            let [h, s, l] = hsl(rgba);
+            match a {
+                255 => format!("hsl({h}, {s}%, {l}%)"),
+                _ => format!("hsla({h}, {s}%, {l}%, {alpha})"),
+            }
+        }
+        _ if a != 255 => format!("rgba({r}, {g}, {b}, {alpha})"),
+        Notation::Rgb => format!("rgb({r}, {g}, {b})"),
+        Notation::Named => {
+            let name = Color::new(r, g, b, 1.0).name();
+            // colors without a latin name, and colors with several names that
+            // aren't picked consistently
+            match name.as_str() {
+                _ if !name.is_ascii() || name.starts_with('#') => format!("#{r:02x}{g:02x}{b:02x}"),
+                "aqua" => "cyan".to_owned(),
+                "fuchsia" => "magenta".to_owned(),
+                "lightgoldenrod" => "lightgoldenrodyellow".to_owned(),
+                _ => name.replace("grey", "gray"),
+            }
+        }
+        Notation::Hex => format!("#{r:02x}{g:02x}{b:02x}"),
+    }
+}
+
+/// The hue, saturation and lightness of a color, rounded to two decimals.
+fn hsl([r, g, b, _]: [u8; 4]) -> [f64; 3] {
+    let (r, g, b) = (
+        f64::from(r) / 255.0,
+        f64::from(g) / 255.0,
+        f64::from(b) / 255.0,
+    );
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+    let (chroma, lightness) = (max - min, (max + min) / 2.0);
+
+    let hue = if chroma == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / chroma + 2.0)
+    } else {
+        60.0 * ((r - g) / chroma + 4.0)
+    };
+    let saturation = if chroma == 0.0 {
+        0.0
+    } else {
+        chroma / (1.0 - (2.0 * lightness - 1.0).abs())
+    };
+
+    [hue, saturation * 100.0, lightness * 100.0].map(|v| (v * 100.0).round() / 100.0)
+}
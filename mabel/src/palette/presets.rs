@@ -0,0 +1,85 @@
+// mabel, declarative pixel art
+// Copyright (c) 2024 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Built-in palettes for the `palette_preset` field.
+
+use color_art::Color;
+
+pub struct Preset {
+    /// The name used by `palette_preset`.
+    pub name: &'static str,
+    pub title: &'static str,
+    pub colors: &'static [u32],
+}
+
+impl Preset {
+    pub fn colors(&self) -> Vec<Color> {
+        self.colors
+            .iter()
+            .map(|c| Color::from_num(*c).unwrap())
+            .collect()
+    }
+}
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "pico-8",
+        title: "PICO-8",
+        colors: &[
+            0x000000, 0x1d2b53, 0x7e2553, 0x008751, 0xab5236, 0x5f574f, 0xc2c3c7, 0xfff1e8,
+            0xff004d, 0xffa300, 0xffec27, 0x00e436, 0x29adff, 0x83769c, 0xff77a8, 0xffccaa,
+        ],
+    },
+    Preset {
+        name: "db16",
+        title: "DawnBringer 16",
+        colors: &[
+            0x140c1c, 0x442434, 0x30346d, 0x4e4a4e, 0x854c30, 0x346524, 0xd04648, 0x757161,
+            0x597dce, 0xd27d2c, 0x8595a1, 0x6daa2c, 0xd2aa99, 0x6dc2ca, 0xdad45e, 0xdeeed6,
+        ],
+    },
+    Preset {
+        name: "db32",
+        title: "DawnBringer 32",
+        colors: &[
+            0x000000, 0x222034, 0x45283c, 0x663931, 0x8f563b, 0xdf7126, 0xd9a066, 0xeec39a,
+            0xfbf236, 0x99e550, 0x6abe30, 0x37946e, 0x4b692f, 0x524b24, 0x323c39, 0x3f3f74,
+            0x306082, 0x5b6ee1, 0x639bff, 0x5fcde4, 0xcbdbfc, 0xffffff, 0x9badb7, 0x847e87,
+            0x696a6a, 0x595652, 0x76428a, 0xac3232, 0xd95763, 0xd77bba, 0x8f974a, 0x8a6f30,
+        ],
+    },
+    Preset {
+        name: "endesga-32",
+        title: "Endesga 32",
+        colors: &[
+            0xbe4a2f, 0xd77643, 0xead4aa, 0xe4a672, 0xb86f50, 0x733e39, 0x3e2731, 0xa22633,
+            0xe43b44, 0xf77622, 0xfeae34, 0xfee761, 0x63c74d, 0x3e8948, 0x265c42, 0x193c3e,
+            0x124e89, 0x0099db, 0x2ce8f5, 0xffffff, 0xc0cbdc, 0x8b9bb4, 0x5a6988, 0x3a4466,
+            0x262b44, 0x181425, 0xff0044, 0x68386c, 0xb55088, 0xf6757a, 0xe8b796, 0xc28569,
+        ],
+    },
+    Preset {
+        name: "gameboy",
+        title: "Game Boy DMG",
+        colors: &[0x0f380f, 0x306230, 0x8bac0f, 0x9bbc0f],
+    },
+    Preset {
+        name: "nes",
+        title: "NES",
+        colors: &[
+            0x000000, 0xfcfcfc, 0xf8f8f8, 0xbcbcbc, 0x7c7c7c, 0xa4e4fc, 0x3cbcfc, 0x0078f8,
+            0x0000fc, 0xb8b8f8, 0x6888fc, 0x0058f8, 0x0000bc, 0xd8b8f8, 0x9878f8, 0x6844fc,
+            0x4428bc, 0xf8b8f8, 0xf878f8, 0xd800cc, 0x940084, 0xf8a4c0, 0xf85898, 0xe40058,
+            0xa80020, 0xf0d0b0, 0xf87858, 0xf83800, 0xa81000, 0xfce0a8, 0xfca044, 0xe45c10,
+            0x881400, 0xf8d878, 0xf8b800, 0xac7c00, 0x503000, 0xd8f878, 0xb8f818, 0x00b800,
+            0x007800, 0xb8f8b8, 0x58d854, 0x00a800, 0x006800, 0xb8f8d8, 0x58f898, 0x00a844,
+            0x005800, 0x00fcfc, 0x00e8d8, 0x008888, 0x004058, 0xf8d8f8, 0x787878,
+        ],
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+}
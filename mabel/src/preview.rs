@@ -0,0 +1,304 @@
+// mabel, declarative pixel art
+// Copyright (c) 2024 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{collections::HashMap, fmt::Write, str::FromStr};
+
+use crate::{Error, Mabel, Pixel, Result};
+
+/// How images are drawn in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// Half-block characters in 24-bit color, two pixels per cell.
+    Truecolor,
+    /// Half-block characters in the 256 color palette.
+    Ansi256,
+    /// The DEC Sixel graphics protocol.
+    Sixel,
+    /// The kitty graphics protocol.
+    Kitty,
+}
+
+impl Protocol {
+    /// Truecolor when `COLORTERM` advertises it, 256 colors otherwise.
+    pub fn detect() -> Self {
+        match std::env::var("COLORTERM").as_deref() {
+            Ok("truecolor" | "24bit") => Self::Truecolor,
+            _ => Self::Ansi256,
+        }
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "truecolor" => Ok(Self::Truecolor),
+            "256" => Ok(Self::Ansi256),
+            "sixel" => Ok(Self::Sixel),
+            "kitty" => Ok(Self::Kitty),
+            _ => Err(
+                format!("Invalid protocol \"{s}\", must be truecolor, 256, sixel or kitty").into(),
+            ),
+        }
+    }
+}
+
+/// The checkerboard transparent pixels are drawn over.
+const CHECKER: [[u8; 3]; 2] = [[204, 204, 204], [153, 153, 153]];
+
+/// How many image pixels a checkerboard square spans in graphics protocols.
+const CHECKER_SIZE: usize = 8;
+
+/// The channel levels of the 6x6x6 color cube of the 256 color palette.
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl Mabel {
+    /// The first frame drawn for the terminal. Half-block previews are one
+    /// cell per two pixels, graphics protocols are scaled by `size`.
+    pub fn preview(&self, protocol: Protocol) -> Result<String> {
+        let frame = self.frames.first().ok_or("The document has no image")?;
+        let (width, height) = (self.width() as usize, self.height() as usize);
+
+        let preview = match protocol {
+            Protocol::Truecolor | Protocol::Ansi256 => {
+                let pixels = frame.pixels();
+                let color = |x: usize, y: usize| {
+                    let pixel = pixels.get(y).and_then(|l| l.get(x));
+                    over_checker(pixel.map_or([0, 0, 0, 0], Pixel::rgba), x + y)
+                };
+
+                half_blocks(width, height, protocol == Protocol::Truecolor, color)
+            }
+            Protocol::Sixel => {
+                let size = usize::from(self.size);
+                let data = self.frame_rgba(frame);
+                sixel(width * size, height * size, |x, y| {
+                    let i = (y * width * size + x) * 4;
+                    let rgba = [data[i], data[i + 1], data[i + 2], data[i + 3]];
+                    over_checker(rgba, x / CHECKER_SIZE + y / CHECKER_SIZE)
+                })
+            }
+            Protocol::Kitty => kitty(
+                self.image_width(),
+                self.image_height(),
+                &self.frame_rgba(frame),
+            ),
+        };
+
+        Ok(preview)
+    }
+}
+
+/// Blends `rgba` over the checkerboard square of parity `square`.
+fn over_checker([r, g, b, a]: [u8; 4], square: usize) -> [u8; 3] {
+    let checker = CHECKER[square % 2];
+    let a = u16::from(a);
+    let blend = |c: u8, k: u8| ((u16::from(c) * a + u16::from(k) * (255 - a)) / 255) as u8;
+
+    [
+        blend(r, checker[0]),
+        blend(g, checker[1]),
+        blend(b, checker[2]),
+    ]
+}
+
+/// Draws two rows per line with `▀`, the top pixel as the foreground and the
+/// bottom one as the background.
+fn half_blocks(
+    width: usize,
+    height: usize,
+    truecolor: bool,
+    color: impl Fn(usize, usize) -> [u8; 3],
+) -> String {
+    let sgr = |layer: u8, [r, g, b]: [u8; 3]| {
+        if truecolor {
+            format!("\x1b[{layer}8;2;{r};{g};{b}m")
+        } else {
+            format!("\x1b[{layer}8;5;{}m", ansi256([r, g, b]))
+        }
+    };
+
+    let mut preview = String::new();
+    for y in (0..height).step_by(2) {
+        for x in 0..width {
+            preview.push_str(&sgr(3, color(x, y)));
+            if y + 1 < height {
+                preview.push_str(&sgr(4, color(x, y + 1)));
+            } else {
+                // the last row of an odd height leaves the bottom half empty
+                preview.push_str("\x1b[49m");
+            }
+            preview.push('▀');
+        }
+        preview.push_str("\x1b[0m\n");
+    }
+
+    preview
+}
+
+/// The closest color of the 256 color palette, from its 6x6x6 cube or its
+/// grayscale ramp.
+pub fn ansi256([r, g, b]: [u8; 3]) -> u8 {
+    let level = |c: u8| {
+        (0..6)
+            .min_by_key(|i| (i32::from(CUBE[*i]) - i32::from(c)).abs())
+            .unwrap() as u8
+    };
+    let distance = |[r2, g2, b2]: [u8; 3]| {
+        [(r, r2), (g, g2), (b, b2)]
+            .iter()
+            .map(|(a, b)| (i32::from(*a) - i32::from(*b)).pow(2))
+            .sum::<i32>()
+    };
+
+    let (cr, cg, cb) = (level(r), level(g), level(b));
+    let cube = [CUBE[cr as usize], CUBE[cg as usize], CUBE[cb as usize]];
+
+    // the grayscale ramp goes from 8 to 238 in steps of 10
+    let average = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+    let step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + step * 10;
+
+    if distance([gray, gray, gray]) < distance(cube) {
+        232 + step
+    } else {
+        16 + 36 * cr + 6 * cg + cb
+    }
+}
+
+/// The color of an index of the 256 color palette, past its 16 system colors.
+fn ansi256_rgb(index: u8) -> [u8; 3] {
+    match index {
+        0..=15 => [0, 0, 0],
+        16..=231 => {
+            let i = index - 16;
+            [
+                CUBE[usize::from(i / 36)],
+                CUBE[usize::from(i / 6 % 6)],
+                CUBE[usize::from(i % 6)],
+            ]
+        }
+        _ => [8 + (index - 232) * 10; 3],
+    }
+}
+
+/// Draws the image as sixels, with up to 256 color registers. Images with
+/// more colors are reduced to the 256 color palette.
+fn sixel(width: usize, height: usize, color: impl Fn(usize, usize) -> [u8; 3]) -> String {
+    let mut pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| color(x, y))
+        .collect::<Vec<_>>();
+
+    let assign = |pixels: &[[u8; 3]]| {
+        let mut registers = HashMap::new();
+        for pixel in pixels {
+            let next = registers.len();
+            registers.entry(*pixel).or_insert(next);
+        }
+        registers
+    };
+
+    let mut registers = assign(&pixels);
+    if registers.len() > 256 {
+        for pixel in &mut pixels {
+            *pixel = ansi256_rgb(ansi256(*pixel));
+        }
+        registers = assign(&pixels);
+    }
+
+    // raster attributes: 1:1 aspect ratio and the image size
+    let mut sixel = format!("\x1bPq\"1;1;{width};{height}");
+    let mut colors = registers.iter().collect::<Vec<_>>();
+    colors.sort_by_key(|(_, i)| **i);
+    for ([r, g, b], i) in &colors {
+        // sixel colors are percentages
+        let percent = |c: u8| u32::from(c) * 100 / 255;
+        let _ = write!(
+            sixel,
+            "#{i};2;{};{};{}",
+            percent(*r),
+            percent(*g),
+            percent(*b)
+        );
+    }
+
+    for band in (0..height).step_by(6) {
+        let mut drawn = false;
+        for (rgb, i) in &colors {
+            let mut line = String::new();
+            let mut used = false;
+            for x in 0..width {
+                let mut bits = 0;
+                for row in 0..6.min(height - band) {
+                    if pixels[(band + row) * width + x] == **rgb {
+                        bits |= 1 << row;
+                    }
+                }
+                used |= bits != 0;
+                line.push(char::from(63 + bits));
+            }
+
+            if used {
+                if drawn {
+                    // return to the start of the band
+                    sixel.push('$');
+                }
+                let _ = write!(sixel, "#{i}{line}");
+                drawn = true;
+            }
+        }
+        sixel.push('-');
+    }
+    sixel.push_str("\x1b\\\n");
+
+    sixel
+}
+
+/// Draws the rgba image with the kitty graphics protocol, split into the
+/// 4096 byte chunks it expects.
+fn kitty(width: u32, height: u32, data: &[u8]) -> String {
+    let encoded = base64(data);
+    let chunks = encoded.as_bytes().chunks(4096).collect::<Vec<_>>();
+
+    let mut kitty = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).unwrap();
+        if i == 0 {
+            let _ = write!(
+                kitty,
+                "\x1b_Ga=T,f=32,s={width},v={height},m={more};{chunk}\x1b\\"
+            );
+        } else {
+            let _ = write!(kitty, "\x1b_Gm={more};{chunk}\x1b\\");
+        }
+    }
+    kitty.push('\n');
+
+    kitty
+}
+
+pub(crate) fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - i * 8));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(ALPHABET[(n >> (18 - i * 6)) as usize & 63]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
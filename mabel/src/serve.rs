@@ -0,0 +1,419 @@
+// mabel, declarative pixel art
+// Copyright (c) 2024 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+use mabel::{sheet::SheetOptions, stamp::IncludeError, Error, Mabel, Result};
+use mabel_eno::HtmlPrinter;
+
+use crate::watch::{documents, modified, INTERVAL};
+
+/// How often an idle event stream is pinged, which notices closed tabs.
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+const STYLE: &str = "
+body { font-family: sans-serif; background: #1e1e2e; color: #cdd6f4; margin: 2rem; }
+a { color: #89b4fa; }
+section { margin-bottom: 2rem; }
+img { image-rendering: pixelated; max-width: 100%;
+      background: repeating-conic-gradient(#ccc 0% 25%, #999 0% 50%) 0 0 / 16px 16px; }
+pre { background: #181825; padding: 1rem; overflow-x: auto; }
+mark { background: #f38ba833; color: inherit; display: inline-block; width: 100%; }
+.error { color: #f38ba8; font-weight: bold; white-space: pre-wrap; }
+.gutter { color: #6c7086; }
+.key { color: #89b4fa; }
+.operator { color: #f9e2af; }
+.comment { color: #6c7086; }
+";
+
+/// Reloads open pages whenever a file in the directory changes.
+const SCRIPT: &str = "new EventSource('/events').onmessage = () => location.reload();";
+
+/// The number of changes seen so far, waited on by the event streams.
+type Changes = Arc<(Mutex<u64>, Condvar)>;
+
+/// Serves the `.eno` files of `dir` on localhost, rendering them on request.
+pub fn serve(dir: &str, port: u16) -> Result<()> {
+    let dir = PathBuf::from(dir);
+    if !dir.is_dir() {
+        return Err(format!("\"{}\" is not a directory", dir.display()).into());
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let changes = Changes::default();
+
+    let watched = dir.clone();
+    let notify = changes.clone();
+    std::thread::spawn(move || watch(&watched, &notify));
+
+    println!(
+        "serving {} on http://127.0.0.1:{port}, press ctrl-c to stop",
+        dir.display()
+    );
+    for stream in listener.incoming().flatten() {
+        let dir = dir.clone();
+        let changes = changes.clone();
+        std::thread::spawn(move || {
+            // the browser going away mid-response isn't worth reporting
+            let _ = handle(stream, &dir, &changes);
+        });
+    }
+
+    Ok(())
+}
+
+/// Counts a change whenever a file under `dir` is added, removed or modified.
+fn watch(dir: &Path, changes: &Changes) {
+    let mut last = snapshot(dir);
+    loop {
+        std::thread::sleep(INTERVAL);
+
+        let current = snapshot(dir);
+        if current != last {
+            last = current;
+            let (count, condvar) = &**changes;
+            *count.lock().unwrap() += 1;
+            condvar.notify_all();
+        }
+    }
+}
+
+fn snapshot(dir: &Path) -> HashMap<PathBuf, Option<std::time::SystemTime>> {
+    let mut files = HashMap::new();
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                let time = modified(&path);
+                files.insert(path, time);
+            }
+        }
+    }
+
+    files
+}
+
+fn handle(stream: TcpStream, dir: &Path, changes: &Changes) -> Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // the headers aren't needed, but have to be read before responding
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or("/"));
+    if method != "GET" {
+        return respond(&stream, "405 Method Not Allowed", "text/plain", b"");
+    }
+
+    let path = decode(target.split('?').next().unwrap_or("/"));
+    match path.as_str() {
+        "/" => respond(
+            &stream,
+            "200 OK",
+            "text/html; charset=utf-8",
+            index(dir)?.as_bytes(),
+        ),
+        "/events" => events(stream, changes),
+        _ => {
+            let file = path
+                .strip_prefix("/image/")
+                .and_then(|name| document(dir, name));
+
+            match file.map(|f| image(&f)) {
+                Some(Ok(png)) => respond(&stream, "200 OK", "image/png", &png),
+                Some(Err(e)) => respond(
+                    &stream,
+                    "500 Internal Server Error",
+                    "text/plain; charset=utf-8",
+                    e.to_string().as_bytes(),
+                ),
+                None => respond(&stream, "404 Not Found", "text/plain", b"Not found"),
+            }
+        }
+    }
+}
+
+fn respond(mut stream: &TcpStream, status: &str, content_type: &str, body: &[u8]) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+
+    Ok(())
+}
+
+/// A server-sent event stream that sends `reload` on every change.
+fn events(mut stream: TcpStream, changes: &Changes) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-store\r\n\r\n"
+    )?;
+    stream.flush()?;
+
+    let (count, condvar) = &**changes;
+    let mut seen = *count.lock().unwrap();
+    loop {
+        let (current, _) = condvar
+            .wait_timeout_while(count.lock().unwrap(), KEEPALIVE, |c| *c == seen)
+            .unwrap();
+
+        if *current == seen {
+            stream.write_all(b": keepalive\n\n")?;
+        } else {
+            seen = *current;
+            stream.write_all(b"data: reload\n\n")?;
+        }
+        stream.flush()?;
+    }
+}
+
+/// The page listing every document, linked to its image or with its error.
+/// Documents are only parsed here, the images are rendered when requested.
+fn index(dir: &Path) -> Result<String> {
+    let mut html = format!(
+        "<!doctype html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>mabel - {}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        escape(&dir.display().to_string()),
+        escape(&dir.display().to_string())
+    );
+
+    let documents = documents(dir)?;
+    if documents.is_empty() {
+        html.push_str("<p>No .eno files yet.</p>\n");
+    }
+    for document in documents {
+        let name = file_name(&document);
+        html.push_str(&format!("<section>\n<h2>{}</h2>\n", escape(&name)));
+        match Mabel::from_file(&document.to_string_lossy()) {
+            Ok(_) => html.push_str(&format!(
+                "<img src=\"/image/{}\" alt=\"{}\">\n",
+                encode(&name),
+                escape(&name)
+            )),
+            Err(e) => html.push_str(&error(&document, &e)),
+        }
+        html.push_str("</section>\n");
+    }
+    html.push_str(&format!("<script>{SCRIPT}</script>\n</body>\n</html>\n"));
+
+    Ok(html)
+}
+
+/// The document in `dir` called `name`. Only the listed documents are
+/// served, never arbitrary paths.
+fn document(dir: &Path, name: &str) -> Option<PathBuf> {
+    documents(dir)
+        .ok()?
+        .into_iter()
+        .find(|d| file_name(d) == name)
+}
+
+/// Renders a document as a png, sprite sheets with their default layout.
+fn image(file: &Path) -> Result<Vec<u8>> {
+    let mabel = Mabel::from_file(&file.to_string_lossy())?;
+    if mabel.sprites.is_empty() {
+        mabel.png()
+    } else {
+        mabel.sheet(SheetOptions::default())?.png()
+    }
+}
+
+/// The error message followed by the document it's in, with the line it's on
+/// highlighted when it's known.
+fn error(file: &Path, e: &Error) -> String {
+    // errors in stamps are shown in the stamped document
+    let (file, source) = match e.downcast_ref::<IncludeError>() {
+        Some(include) => (
+            include.includes.last().map_or(file, PathBuf::as_path),
+            include.source.as_ref(),
+        ),
+        None => (file, e.as_ref()),
+    };
+    let line = source
+        .downcast_ref::<mabel_eno::Error>()
+        .map(|e| e.line as usize);
+
+    let content = std::fs::read_to_string(file).unwrap_or_default();
+    let snippet = match mabel_eno::parse_with_printer(&content, Box::new(HtmlPrinter)) {
+        Ok(document) => document.snippet(),
+        // documents with syntax errors can't be highlighted
+        Err(_) => content
+            .lines()
+            .enumerate()
+            .map(|(i, l)| format!("<span class=\"gutter\"> {:>3} </span> {}", i + 1, escape(l)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+
+    let snippet = snippet
+        .lines()
+        .enumerate()
+        .map(|(i, l)| {
+            if Some(i + 1) == line {
+                format!("<mark>{l}</mark>")
+            } else {
+                l.to_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "<p class=\"error\">{}</p>\n<pre>{snippet}</pre>\n",
+        escape(&e.to_string())
+    )
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Percent-encodes everything but unreserved url characters.
+fn encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(b).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+fn decode(s: &str) -> String {
+    let mut bytes = vec![];
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        let hex = tail
+            .get(..2)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+
+        match (b, hex) {
+            (b'%', Some(byte)) => {
+                bytes.push(byte);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, contents) in files {
+            std::fs::write(dir.join(file), contents).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape(r#"<a href="x">&</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("walk-01_a.b~c.eno"), "walk-01_a.b~c.eno");
+        assert_eq!(encode("my sprite/?.eno"), "my%20sprite%2F%3F.eno");
+        assert_eq!(encode("été"), "%C3%A9t%C3%A9");
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("/image/my%20sprite.eno"), "/image/my sprite.eno");
+        assert_eq!(decode("%C3%A9t%C3%A9"), "été");
+        // broken escapes are kept as they are
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode("%zz%4"), "%zz%4");
+
+        for name in ["my sprite.eno", "50% off?.eno", "été.eno"] {
+            assert_eq!(decode(&encode(name)), name);
+        }
+    }
+
+    #[test]
+    fn test_document() {
+        let dir = dir(
+            "mabel-serve-document",
+            &[("a b.eno", ""), ("notes.txt", "")],
+        );
+        std::fs::write(dir.parent().unwrap().join("outside.eno"), "").unwrap();
+
+        assert_eq!(document(&dir, "a b.eno"), Some(dir.join("a b.eno")));
+        // only documents in the directory itself are served
+        assert_eq!(document(&dir, "notes.txt"), None);
+        assert_eq!(document(&dir, "../outside.eno"), None);
+        assert_eq!(document(&dir, "missing.eno"), None);
+    }
+
+    #[test]
+    fn test_index() {
+        let documents = dir(
+            "mabel-serve-index",
+            &[
+                (
+                    "dot.eno",
+                    "palette:\na = #ff0000\n-- pixels\na\n-- pixels\n",
+                ),
+                (
+                    "<broken>.eno",
+                    "palette:\na = nope\n-- pixels\na\n-- pixels\n",
+                ),
+            ],
+        );
+
+        let html = index(&documents).unwrap();
+        assert!(html.contains("<img src=\"/image/dot.eno\" alt=\"dot.eno\">"));
+        assert!(html.contains("<h2>&lt;broken&gt;.eno</h2>\n<p class=\"error\">"));
+        assert!(!html.contains("/image/%3Cbroken%3E.eno"));
+
+        let empty = dir("mabel-serve-empty", &[]);
+        assert!(index(&empty).unwrap().contains("No .eno files yet."));
+    }
+}
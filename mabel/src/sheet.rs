@@ -0,0 +1,366 @@
+// mabel, declarative pixel art
+// Copyright (c) 2024 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fmt::Write, path::Path, str::FromStr};
+
+use image::{imageops, Rgba, RgbaImage};
+use mabel_aseprite::util::extrude_border;
+use mabel_eno::Section;
+
+use crate::{layer, palette, Context, Error, Layer, Mabel, Pixel, Result};
+
+#[derive(Debug, Clone)]
+pub struct Sprite {
+    pub name: String,
+    /// The layers of the sprite, from bottom to top.
+    pub layers: Vec<Layer>,
+}
+
+impl Sprite {
+    pub fn new(name: impl Into<String>, layers: Vec<Layer>) -> Self {
+        Self {
+            name: name.into(),
+            layers,
+        }
+    }
+
+    /// The composited pixels of all visible layers.
+    pub fn pixels(&self) -> Vec<Vec<Pixel>> {
+        layer::composite(&self.layers)
+    }
+
+    pub fn pixels_height(&self) -> u32 {
+        self.layers
+            .iter()
+            .map(Layer::pixels_height)
+            .max()
+            .unwrap_or(0) as u32
+    }
+
+    pub fn pixels_width(&self) -> u32 {
+        self.layers
+            .iter()
+            .map(Layer::pixels_width)
+            .max()
+            .unwrap_or(0) as u32
+    }
+}
+
+/// How sprites are laid out in a sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Packing {
+    /// Every sprite side by side in a single row.
+    Rows,
+    /// Every sprite stacked in a single column.
+    Columns,
+    /// Sprites packed into shelves of a roughly square sheet, tallest first.
+    #[default]
+    Packed,
+}
+
+impl FromStr for Packing {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "rows" => Ok(Self::Rows),
+            "columns" => Ok(Self::Columns),
+            "packed" => Ok(Self::Packed),
+            _ => Err(format!("Invalid packing \"{s}\", must be rows, columns or packed").into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SheetOptions {
+    pub packing: Packing,
+    /// The space between sprites, in image pixels.
+    pub padding: u32,
+    /// Repeats the edges of every sprite one pixel outwards, which keeps
+    /// neighbouring sprites from bleeding in when the sheet is filtered.
+    pub extrude: bool,
+}
+
+/// A sprite's area in the sheet image, excluding any extrusion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug)]
+pub struct Sheet {
+    pub image: RgbaImage,
+    /// The sprite names and areas, in document order.
+    pub sprites: Vec<(String, Rect)>,
+    /// The scale the sprites were drawn at.
+    pub size: u8,
+}
+
+impl Mabel {
+    /// Draws the document sprites into a single sheet image.
+    pub fn sheet(&self, options: SheetOptions) -> Result<Sheet> {
+        if self.sprites.is_empty() {
+            return Err("The document has no sprites".into());
+        }
+
+        let border = u32::from(options.extrude);
+        let cells = self
+            .sprites
+            .iter()
+            .map(|sprite| {
+                let image = self.sprite_image(sprite)?;
+                Ok(if options.extrude {
+                    extrude_border(image)
+                } else {
+                    image
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let sizes = cells.iter().map(RgbaImage::dimensions).collect::<Vec<_>>();
+        let positions = pack(&sizes, options.packing, options.padding);
+
+        let width = positions
+            .iter()
+            .zip(&sizes)
+            .map(|((x, _), (w, _))| x + w)
+            .max()
+            .unwrap_or(0);
+        let height = positions
+            .iter()
+            .zip(&sizes)
+            .map(|((_, y), (_, h))| y + h)
+            .max()
+            .unwrap_or(0);
+
+        let mut image = RgbaImage::new(width, height);
+        let mut sprites = vec![];
+        for ((sprite, cell), (x, y)) in self.sprites.iter().zip(&cells).zip(positions) {
+            imageops::replace(&mut image, cell, i64::from(x), i64::from(y));
+            sprites.push((
+                sprite.name.clone(),
+                Rect {
+                    x: x + border,
+                    y: y + border,
+                    width: cell.width() - border * 2,
+                    height: cell.height() - border * 2,
+                },
+            ));
+        }
+
+        Ok(Sheet {
+            image,
+            sprites,
+            size: self.size,
+        })
+    }
+
+    /// The scaled image of a sprite.
+    fn sprite_image(&self, sprite: &Sprite) -> Result<RgbaImage> {
+        let (width, height) = (sprite.pixels_width(), sprite.pixels_height());
+        if width == 0 || height == 0 {
+            return Err(format!("Sprite \"{}\" has no pixels", sprite.name).into());
+        }
+
+        let size = u32::from(self.size);
+        let pixels = sprite.pixels();
+
+        Ok(RgbaImage::from_fn(width * size, height * size, |x, y| {
+            let pixel = pixels
+                .get((y / size) as usize)
+                .and_then(|l| l.get((x / size) as usize));
+            Rgba(pixel.map_or([0, 0, 0, 0], Pixel::rgba))
+        }))
+    }
+}
+
+impl Sheet {
+    pub fn save_png(&self, path: &str) -> Result<()> {
+        Ok(std::fs::write(path, self.png()?)?)
+    }
+
+    /// The sheet image encoded as a png.
+    pub fn png(&self) -> Result<Vec<u8>> {
+        let mut data = vec![];
+
+        let mut img = png::Encoder::new(&mut data, self.image.width(), self.image.height());
+        img.set_color(png::ColorType::Rgba);
+        img.set_depth(png::BitDepth::Eight);
+
+        let mut writer = img.write_header()?;
+        writer.write_image_data(self.image.as_raw())?;
+        writer.finish()?;
+
+        Ok(data)
+    }
+
+    /// Writes the sprite areas as json or eno depending on the extension of
+    /// `path`, `image` is the sheet image path recorded in the data.
+    pub fn save_data(&self, path: &str, image: &str) -> Result<()> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+
+        let data = match extension.as_deref() {
+            Some("json") => self.to_json(image),
+            Some("eno") => self.to_eno(image),
+            _ => return Err("Unsupported sheet data format, expected a .json or .eno file".into()),
+        };
+
+        Ok(std::fs::write(path, data)?)
+    }
+
+    /// The sprite areas in the json hash layout of aseprite's `--data`.
+    pub fn to_json(&self, image: &str) -> String {
+        let mut json = String::from("{\n  \"frames\": {");
+        for (i, (name, rect)) in self.sprites.iter().enumerate() {
+            let comma = if i + 1 < self.sprites.len() { "," } else { "" };
+            let _ = write!(
+                json,
+                "\n    {}: {{ \"frame\": {{ \"x\": {}, \"y\": {}, \"w\": {}, \"h\": {} }} }}{comma}",
+                json_string(name),
+                rect.x,
+                rect.y,
+                rect.width,
+                rect.height
+            );
+        }
+
+        let _ = write!(
+            json,
+            "\n  }},\n  \"meta\": {{\n    \"app\": \"mabel\",\n    \"version\": \"{}\",\n    \"image\": {},\n    \"format\": \"RGBA8888\",\n    \"size\": {{ \"w\": {}, \"h\": {} }},\n    \"scale\": \"{}\"\n  }}\n}}\n",
+            env!("CARGO_PKG_VERSION"),
+            json_string(image),
+            self.image.width(),
+            self.image.height(),
+            self.size
+        );
+
+        json
+    }
+
+    /// The sprite areas as an eno document with a `sprite` section each.
+    pub fn to_eno(&self, image: &str) -> String {
+        let mut eno = format!(
+            "image: {image}\nwidth: {}\nheight: {}\n",
+            self.image.width(),
+            self.image.height()
+        );
+        for (name, rect) in &self.sprites {
+            let _ = write!(
+                eno,
+                "\n# sprite\nname: {name}\nx: {}\ny: {}\nwidth: {}\nheight: {}\n",
+                rect.x, rect.y, rect.width, rect.height
+            );
+        }
+
+        eno
+    }
+}
+
+/// Reads a `sprite` section, whose `palette` entries override the document
+/// palette for that sprite alone.
+pub(crate) fn parse(section: &Section, context: &Context) -> Result<Sprite> {
+    let name = section.field("name")?.required_value::<String>()?;
+
+    let mut palette = context.palette.clone();
+    palette.extend(palette::parse_entries(
+        &section.field("palette")?.attributes()?,
+        context.key_width,
+        None,
+    )?);
+    let context = Context {
+        palette: &palette,
+        ..*context
+    };
+
+    let (mut layers, stamps) = crate::parse_layers(
+        section.elements(),
+        section.optional_embed("pixels")?,
+        &context,
+    )
+    .map_err(|e| format!("Sprite \"{name}\": {e}"))?;
+    layers.extend(stamps);
+
+    Ok(Sprite::new(name, layers))
+}
+
+/// The top-left corner of every cell of `sizes`, with `padding` between them.
+fn pack(sizes: &[(u32, u32)], packing: Packing, padding: u32) -> Vec<(u32, u32)> {
+    match packing {
+        Packing::Rows => {
+            let mut x = 0;
+            sizes
+                .iter()
+                .map(|(w, _)| {
+                    let position = (x, 0);
+                    x += w + padding;
+                    position
+                })
+                .collect()
+        }
+        Packing::Columns => {
+            let mut y = 0;
+            sizes
+                .iter()
+                .map(|(_, h)| {
+                    let position = (0, y);
+                    y += h + padding;
+                    position
+                })
+                .collect()
+        }
+        Packing::Packed => {
+            // aim for a square sheet, but never narrower than the widest sprite
+            let area = sizes
+                .iter()
+                .map(|(w, h)| u64::from(w + padding) * u64::from(h + padding))
+                .sum::<u64>();
+            let widest = sizes.iter().map(|(w, _)| *w).max().unwrap_or(0);
+            let width = widest.max((area as f64).sqrt().ceil() as u32);
+
+            let mut order = (0..sizes.len()).collect::<Vec<_>>();
+            order.sort_by_key(|i| std::cmp::Reverse(sizes[*i].1));
+
+            let mut positions = vec![(0, 0); sizes.len()];
+            let (mut x, mut y, mut shelf) = (0, 0, 0);
+            for i in order {
+                let (w, h) = sizes[i];
+                if x > 0 && x + w > width {
+                    x = 0;
+                    y += shelf + padding;
+                    shelf = 0;
+                }
+
+                positions[i] = (x, y);
+                x += w + padding;
+                shelf = shelf.max(h);
+            }
+
+            positions
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", u32::from(c));
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+
+    json
+}
@@ -0,0 +1,132 @@
+// mabel, declarative pixel art
+// Copyright (c) 2024 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fmt, path::PathBuf};
+
+use mabel_eno::Section;
+
+use crate::{Context, Error, Layer, Mabel, Pixel, Result};
+
+/// An error in a stamped document, along with the chain of documents that
+/// included it.
+#[derive(Debug)]
+pub struct IncludeError {
+    /// The canonical paths of the documents, outermost first.
+    pub includes: Vec<PathBuf>,
+    pub source: Error,
+}
+
+impl fmt::Display for IncludeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // a cycle names the whole chain already
+        if self.source.is::<Cycle>() {
+            return write!(f, "{}", self.source);
+        }
+
+        write!(
+            f,
+            "{}\n  included from {}",
+            self.source,
+            chain(&self.includes)
+        )
+    }
+}
+
+impl std::error::Error for IncludeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// A stamp that includes itself, as the chain of documents back to it.
+#[derive(Debug)]
+struct Cycle(Vec<PathBuf>);
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Stamp cycle {}", chain(&self.0))
+    }
+}
+
+impl std::error::Error for Cycle {}
+
+/// Reads a `stamp` section into a layer holding the stamped document, placed
+/// at its offset and remapped onto the host palette.
+pub(crate) fn parse(section: &Section, context: &Context) -> Result<Layer> {
+    let file = section.field("file")?.required_value::<String>()?;
+    let x = section
+        .field("x")?
+        .optional_value()?
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(0);
+    let y = section
+        .field("y")?
+        .optional_value()?
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(0);
+    let flip = section.field("flip")?.optional_value()?;
+    let (flip_x, flip_y) = match flip.as_deref() {
+        None => (false, false),
+        Some("horizontal") => (true, false),
+        Some("vertical") => (false, true),
+        Some("both") => (true, true),
+        Some(flip) => {
+            return Err(
+                format!("Invalid flip \"{flip}\", must be horizontal, vertical or both").into(),
+            )
+        }
+    };
+
+    let path = context.dir.join(&file);
+    let mut includes = context.includes.to_vec();
+    includes.push(
+        std::fs::canonicalize(&path)
+            .map_err(|e| format!("Failed to read stamp \"{}\": {e}", path.display()))?,
+    );
+    if context.includes.contains(includes.last().unwrap()) {
+        return Err(Box::new(Cycle(includes)));
+    }
+    context.dependencies.borrow_mut().push(path.clone());
+
+    // stamp colors take the host color of the same key, when there's one
+    let stamp = Mabel::read(&path, &includes, Some(context.palette)).map_err(|e| -> Error {
+        if e.is::<IncludeError>() {
+            e
+        } else {
+            Box::new(IncludeError {
+                includes: includes.clone(),
+                source: e,
+            })
+        }
+    })?;
+    context
+        .dependencies
+        .borrow_mut()
+        .extend(stamp.dependencies.iter().cloned());
+
+    let mut pixels = stamp.pixels();
+    pixels.resize(stamp.height() as usize, vec![]);
+    for line in &mut pixels {
+        line.resize(stamp.width() as usize, Pixel::Trans);
+        if flip_x {
+            line.reverse();
+        }
+        line.splice(0..0, vec![Pixel::Trans; x]);
+    }
+    if flip_y {
+        pixels.reverse();
+    }
+    pixels.splice(0..0, vec![vec![]; y]);
+
+    Ok(Layer::new(file, pixels))
+}
+
+fn chain(includes: &[PathBuf]) -> String {
+    includes
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
@@ -0,0 +1,149 @@
+// mabel, declarative pixel art
+// Copyright (c) 2024 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{sheet::Rect, Mabel, Pixel, Result};
+
+impl Mabel {
+    /// Writes the first frame as an svg.
+    pub fn save_svg(&self, path: &str, classes: bool) -> Result<()> {
+        Ok(std::fs::write(path, self.to_svg(classes))?)
+    }
+
+    /// The first frame as an svg of merged rectangles, one unit per pixel and
+    /// scaled by `size`. With `classes`, rectangles are grouped by palette key
+    /// in groups classed `key-<key>`.
+    pub fn to_svg(&self, classes: bool) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" shape-rendering=\"crispEdges\">\n",
+            self.image_width(),
+            self.image_height(),
+            self.width(),
+            self.height()
+        );
+
+        let rects = rects(&self.pixels());
+        if classes {
+            // the first key of each color, in declaration order
+            let mut keys = HashMap::new();
+            for (key, pixel) in &self.palette {
+                keys.entry(pixel.rgba()).or_insert(key.as_str());
+            }
+
+            let mut groups = Vec::<([u8; 4], Vec<Rect>)>::new();
+            for (color, rect) in rects {
+                match groups.iter_mut().find(|(c, _)| *c == color) {
+                    Some((_, group)) => group.push(rect),
+                    None => groups.push((color, vec![rect])),
+                }
+            }
+            let index = |color| self.palette.values().position(|p| p.rgba() == color);
+            groups.sort_by_key(|(color, _)| index(*color).unwrap_or(usize::MAX));
+
+            for (color, group) in groups {
+                let class = keys
+                    .get(&color)
+                    .map(|k| format!(" class=\"{}\"", class_name(k)))
+                    .unwrap_or_default();
+                let _ = writeln!(svg, "<g{class}{}>", fill(color));
+                for rect in group {
+                    let _ = writeln!(svg, "{}", rect_element(&rect, ""));
+                }
+                svg.push_str("</g>\n");
+            }
+        } else {
+            for (color, rect) in rects {
+                let _ = writeln!(svg, "{}", rect_element(&rect, &fill(color)));
+            }
+        }
+        svg.push_str("</svg>\n");
+
+        svg
+    }
+}
+
+/// Merges the visible pixels into rectangles, joining runs of a color in a
+/// row and then identical runs of consecutive rows.
+fn rects(pixels: &[Vec<Pixel>]) -> Vec<([u8; 4], Rect)> {
+    let mut rects = vec![];
+    let mut open = Vec::<([u8; 4], Rect)>::new();
+    for (y, line) in pixels.iter().enumerate() {
+        let mut next = vec![];
+        let mut x = 0;
+        while x < line.len() {
+            let color = line[x].rgba();
+            let width = line[x..].iter().take_while(|p| p.rgba() == color).count();
+
+            if color[3] != 0 {
+                let (x, width) = (x as u32, width as u32);
+                let run = match open
+                    .iter()
+                    .position(|(c, r)| *c == color && r.x == x && r.width == width)
+                {
+                    Some(i) => {
+                        let (_, mut rect) = open.swap_remove(i);
+                        rect.height += 1;
+                        (color, rect)
+                    }
+                    None => (
+                        color,
+                        Rect {
+                            x,
+                            y: y as u32,
+                            width,
+                            height: 1,
+                        },
+                    ),
+                };
+                next.push(run);
+            }
+            x += width;
+        }
+
+        rects.append(&mut open);
+        open = next;
+    }
+    rects.append(&mut open);
+    rects.sort_by_key(|(_, r)| (r.y, r.x));
+
+    rects
+}
+
+fn rect_element(rect: &Rect, fill: &str) -> String {
+    format!(
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"{fill}/>",
+        rect.x, rect.y, rect.width, rect.height
+    )
+}
+
+fn fill([r, g, b, a]: [u8; 4]) -> String {
+    let mut fill = format!(" fill=\"#{r:02x}{g:02x}{b:02x}\"");
+    if a != 255 {
+        let opacity = format!("{:.3}", f64::from(a) / 255.0);
+        let _ = write!(
+            fill,
+            " fill-opacity=\"{}\"",
+            opacity.trim_end_matches('0').trim_end_matches('.')
+        );
+    }
+
+    fill
+}
+
+/// A css class for a palette key, characters other than ascii letters and
+/// digits are written as their code point.
+fn class_name(key: &str) -> String {
+    let mut class = String::from("key-");
+    for c in key.chars() {
+        if c.is_ascii_alphanumeric() {
+            class.push(c);
+        } else {
+            let _ = write!(class, "u{:x}", u32::from(c));
+        }
+    }
+
+    class
+}
@@ -0,0 +1,32 @@
+mod animation;
+#[cfg(feature = "aseprite")]
+mod aseprite;
+mod format;
+mod ico;
+mod import;
+mod indexed;
+mod key_width;
+mod layer;
+mod mirror;
+mod palette;
+mod preview;
+mod sheet;
+mod stamp;
+mod svg;
+mod unicode;
+
+use crate::{Mabel, Pixel};
+
+/// Parses an eno document.
+pub(crate) fn mabel(eno: &str) -> crate::Result<Mabel> {
+    Mabel::from(&mabel_eno::parse(eno)?)
+}
+
+/// The pixels of the first frame as rgba.
+pub(crate) fn rgba(mabel: &Mabel) -> Vec<Vec<[u8; 4]>> {
+    mabel
+        .pixels()
+        .iter()
+        .map(|line| line.iter().map(Pixel::rgba).collect())
+        .collect()
+}
@@ -0,0 +1,118 @@
+use indoc::indoc;
+
+use crate::{tests::mabel, Mabel};
+
+/// A red pixel moving right over three frames, the second one held longer.
+fn animation(loops: u32) -> Mabel {
+    mabel(&format!(
+        indoc! {"
+            duration: 150
+            loop: {}
+            palette:
+            r = #ff0000
+
+            # frame
+            -- pixels
+            r
+            -- pixels
+
+            # frame
+            duration: 300
+            -- pixels
+             r
+            -- pixels
+
+            # frame
+            -- pixels
+              r
+            -- pixels
+        "},
+        loops
+    ))
+    .unwrap()
+}
+
+#[test]
+fn test_animation_frames() {
+    let animation = animation(0);
+    assert!(animation.is_animated());
+    assert_eq!(animation.frames.len(), 3);
+    assert_eq!(animation.frames[1].duration, Some(300));
+    let durations = animation.frames.iter().map(|f| animation.frame_duration(f));
+    assert_eq!(durations.collect::<Vec<_>>(), [150, 300, 150]);
+    assert_eq!(animation.width(), 3);
+    assert_eq!(animation.frames[2].pixels()[0][2].rgba(), [255, 0, 0, 255]);
+
+    let error = mabel(indoc! {"
+        palette:
+        r = #ff0000
+
+        -- pixels
+        r
+        -- pixels
+
+        # frame
+        -- pixels
+        r
+        -- pixels
+    "})
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "A document with frames can't have top-level pixels or layers"
+    );
+}
+
+#[test]
+fn test_animation_apng() {
+    for loops in [0, 1, 3] {
+        let data = animation(loops).png().unwrap();
+        let mut reader = png::Decoder::new(data.as_slice()).read_info().unwrap();
+        let control = reader.info().animation_control().unwrap();
+        assert_eq!((control.num_frames, control.num_plays), (3, loops));
+
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let mut delays = vec![];
+        let mut frames = vec![];
+        for _ in 0..3 {
+            let info = reader.next_frame(&mut buffer).unwrap();
+            let frame = reader.info().frame_control().unwrap();
+            delays.push((frame.delay_num, frame.delay_den));
+            frames.push(buffer[..info.buffer_size()].to_vec());
+        }
+        assert_eq!(delays, [(150, 1000), (300, 1000), (150, 1000)]);
+        // the red pixel is the first, second then third of the row
+        for (i, frame) in frames.iter().enumerate() {
+            assert_eq!(frame[i * 4..i * 4 + 4], [255, 0, 0, 255]);
+        }
+    }
+}
+
+#[test]
+fn test_animation_gif() {
+    let cases = [
+        (0, gif::Repeat::Infinite),
+        (1, gif::Repeat::Finite(0)),
+        (3, gif::Repeat::Finite(2)),
+        (100_000, gif::Repeat::Finite(u16::MAX)),
+    ];
+    for (loops, repeat) in cases {
+        let path = std::env::temp_dir().join(format!("mabel-test-animation-{loops}.gif"));
+        animation(loops).save_gif(path.to_str().unwrap()).unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options
+            .read_info(std::fs::File::open(&path).unwrap())
+            .unwrap();
+        let mut delays = vec![];
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+            let red = delays.len() - 1;
+            assert_eq!(frame.buffer[red * 4..red * 4 + 4], [255, 0, 0, 255]);
+        }
+        // gif delays are in hundredths of a second
+        assert_eq!(delays, [15, 30, 15]);
+        assert_eq!(decoder.repeat(), repeat);
+    }
+}
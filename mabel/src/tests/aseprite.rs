@@ -0,0 +1,960 @@
+use image::{Rgba, RgbaImage};
+use indoc::indoc;
+use mabel_aseprite::{
+    writer::{AsepriteWriter, NewCel, NewColor, NewFrame, NewLayer, NewTag, NewTileset},
+    AnimationDirection, AsepriteFile, BlendMode, LayerFlags, LayerType, PixelFormat, Slice, Slice9,
+    SliceKey, UserData,
+};
+
+use crate::{
+    aseprite::{save_to_eno, to_eno, ConvertOptions, Frames, KeyOptions, Order, PixelSize},
+    palette::key,
+    palette::Notation,
+    tests::mabel,
+    Mabel, Pixel,
+};
+
+#[test]
+fn test_aseprite_round_trip() {
+    let mabel = mabel(indoc! {"
+        size: 4
+        width: 3
+        duration: 80
+        palette:
+        z = #0000ff
+        a = #ff0000
+        h = rgba(0, 255, 0, 0.2)
+
+        # frame
+        duration: 200
+
+        -- pixels
+        az
+        h a
+        -- pixels
+
+        # frame
+
+        -- pixels
+        za
+        -- pixels
+    "})
+    .unwrap();
+    let ase = AsepriteFile::read(&mabel.to_aseprite().unwrap()[..]).unwrap();
+
+    // unscaled, padded to the document width
+    assert_eq!(ase.size(), (3, 2));
+    assert_eq!(ase.num_frames(), 2);
+    assert_eq!(ase.num_layers(), 1);
+    assert_eq!(
+        ase.pixel_format(),
+        PixelFormat::Indexed {
+            transparent_color_index: 3
+        }
+    );
+
+    let palette = ase.palette().unwrap();
+    let colors = (0..palette.num_colors())
+        .map(|i| {
+            let color = palette.color(i).unwrap();
+            (color.raw_rgba8(), color.name())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        colors,
+        [
+            ([0, 0, 255, 255], Some("z")),
+            ([255, 0, 0, 255], Some("a")),
+            ([0, 255, 0, 51], Some("h")),
+            ([0, 0, 0, 0], None),
+        ]
+    );
+
+    assert_eq!(ase.frame(0).duration(), 200);
+    assert_eq!(ase.frame(1).duration(), 80);
+    let image = ase.frame(0).image();
+    assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+    assert_eq!(image.get_pixel(1, 0).0, [0, 0, 255, 255]);
+    assert_eq!(image.get_pixel(2, 0).0[3], 0);
+    assert_eq!(image.get_pixel(0, 1).0, [0, 255, 0, 51]);
+    assert_eq!(image.get_pixel(1, 1).0[3], 0);
+    assert_eq!(ase.frame(1).image().get_pixel(1, 0).0, [255, 0, 0, 255]);
+}
+
+#[test]
+fn test_aseprite_rgba() {
+    let colors = (0..300)
+        .map(|i| format!("{} = rgb({}, {}, 0)", key(i, 2), i % 256, i / 256))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let pixels = (0..300).map(|i| key(i, 2)).collect::<String>();
+    let eno = format!("key_width: 2\npalette:\n{colors}\n\n-- pixels\n{pixels}\n-- pixels\n");
+
+    // 300 colors don't fit an indexed file
+    let ase = AsepriteFile::read(&mabel(&eno).unwrap().to_aseprite().unwrap()[..]).unwrap();
+    assert_eq!(ase.pixel_format(), PixelFormat::Rgba);
+    assert_eq!(ase.palette().unwrap().num_colors(), 301);
+    assert_eq!(ase.frame(0).image().get_pixel(299, 0).0, [43, 1, 0, 255]);
+}
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+/// A 2x1 animation of a red pixel moving right and a blue one staying put.
+fn animation() -> AsepriteWriter {
+    let mut ase = AsepriteWriter::new(2, 1);
+    ase.layers.push(NewLayer::new("Layer 1"));
+    for (i, duration) in [100, 100, 250].into_iter().enumerate() {
+        let image = RgbaImage::from_fn(2, 1, |x, _| match (x, i) {
+            (0, 0) | (1, 1) => Rgba(RED),
+            _ => Rgba(BLUE),
+        });
+        let mut frame = NewFrame::new(duration);
+        frame.cels.push(NewCel::image(0, image));
+        ase.frames.push(frame);
+    }
+    ase.tags.push(NewTag::new("end", 1, 2));
+    ase
+}
+
+/// The first document `ase` converts to, read back.
+fn convert(ase: &AsepriteFile, options: &ConvertOptions) -> Mabel {
+    mabel(&to_eno(ase, options).unwrap()[0]).unwrap()
+}
+
+fn frames(frames: Frames) -> ConvertOptions {
+    ConvertOptions {
+        frames,
+        ..ConvertOptions::default()
+    }
+}
+
+fn first_pixels(mabel: &Mabel) -> Vec<[u8; 4]> {
+    mabel
+        .frames
+        .iter()
+        .map(|f| f.pixels()[0][0].rgba())
+        .collect()
+}
+
+#[test]
+fn test_aseprite_frames() {
+    let mabel = convert(&ase_file(animation()), &frames(Frames::All));
+
+    assert_eq!(first_pixels(&mabel), [RED, BLUE, BLUE]);
+    let durations = mabel
+        .frames
+        .iter()
+        .map(|f| mabel.frame_duration(f))
+        .collect::<Vec<_>>();
+    assert_eq!(durations, [100, 100, 250]);
+}
+
+#[test]
+fn test_aseprite_frame_range() {
+    let ase = ase_file(animation());
+    let mabel = convert(&ase, &frames("0-1".parse().unwrap()));
+    assert_eq!(first_pixels(&mabel), [RED, BLUE]);
+    // a shared duration is written for the whole document
+    assert_eq!(mabel.duration, 100);
+    assert!(mabel.frames.iter().all(|f| f.duration.is_none()));
+
+    let mabel = convert(&ase, &frames("2".parse().unwrap()));
+    assert_eq!(mabel.frames.len(), 1);
+
+    let result = to_eno(&ase, &frames(Frames::Range(1, 3)));
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Frame range 1-3 is outside the 3 frames of the file"
+    );
+    assert!("1-".parse::<Frames>().is_err());
+}
+
+#[test]
+fn test_aseprite_tag() {
+    let mabel = convert(
+        &ase_file(animation()),
+        &frames(Frames::Tag("end".to_owned())),
+    );
+    assert_eq!(first_pixels(&mabel), [BLUE, BLUE]);
+    assert_eq!(mabel.frames[0].pixels()[0][1].rgba(), RED);
+}
+
+#[test]
+fn test_aseprite_flat() {
+    // the only conversion through files, every other test converts in memory
+    let dir = std::env::temp_dir().join("mabel-flat");
+    std::fs::create_dir_all(&dir).unwrap();
+    let ase = dir.join("walk.aseprite");
+    animation().write_file(&ase).unwrap();
+    let output = dir.join("walk.eno");
+    save_to_eno(
+        ase.to_str().unwrap(),
+        output.to_str().unwrap(),
+        &ConvertOptions {
+            frames: Frames::All,
+            flat: true,
+            ..ConvertOptions::default()
+        },
+    )
+    .unwrap();
+
+    let documents = (0..3)
+        .map(|i| Mabel::from_file(dir.join(format!("walk-{i}.eno")).to_str().unwrap()).unwrap())
+        .collect::<Vec<_>>();
+    assert!(documents.iter().all(|m| m.frames.len() == 1));
+    assert_eq!(documents[1].pixels()[0][1].rgba(), RED);
+    // each document keeps the duration of its frame
+    let durations = documents.iter().map(|m| m.frame_duration(&m.frames[0]));
+    assert_eq!(durations.collect::<Vec<_>>(), [100, 100, 250]);
+    // every document has the same keys for the same colors
+    let palette = |m: &Mabel| {
+        m.palette
+            .iter()
+            .map(|(k, p)| (k.clone(), p.rgba()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(palette(&documents[0]), palette(&documents[2]));
+}
+
+#[test]
+fn test_aseprite_layers() {
+    // a background with a group of two layers, the second nested in a hidden group
+    let mut ase = AsepriteWriter::new(2, 1);
+    let mut characters = NewLayer::new("Characters");
+    characters.is_group = true;
+    let mut hero = NewLayer::new("Hero");
+    hero.child_level = 1;
+    hero.opacity = 128;
+    hero.blend_mode = BlendMode::Multiply;
+    let mut hidden = NewLayer::new("Hidden");
+    hidden.is_group = true;
+    hidden.child_level = 1;
+    hidden.flags.remove(LayerFlags::VISIBLE);
+    let mut ghost = NewLayer::new("Ghost");
+    ghost.child_level = 2;
+    ase.layers = vec![NewLayer::new("Background"), characters, hero, hidden, ghost];
+
+    let mut frame = NewFrame::new(100);
+    frame
+        .cels
+        .push(NewCel::image(0, RgbaImage::from_pixel(2, 1, Rgba(RED))));
+    let blue = RgbaImage::from_fn(2, 1, |x, _| Rgba([0, 0, 255, 255 * x as u8]));
+    frame.cels.push(NewCel::image(2, blue.clone()));
+    frame.cels.push(NewCel::image(4, blue));
+    ase.frames.push(frame);
+
+    let options = ConvertOptions {
+        layers: true,
+        ..ConvertOptions::default()
+    };
+    let mabel = convert(&ase_file(ase), &options);
+
+    let layers = &mabel.frames[0].layers;
+    let names = layers
+        .iter()
+        .map(|l| (l.name.as_str(), l.group.as_deref()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            ("Background", None),
+            ("Hero", Some("Characters")),
+            ("Ghost", Some("Characters/Hidden")),
+        ]
+    );
+    assert_eq!(layers[1].opacity, 128);
+    assert_eq!(layers[1].blend_mode, BlendMode::Multiply);
+    // layers in hidden groups are hidden themselves
+    assert!(layers[0].visible && layers[1].visible && !layers[2].visible);
+    assert_eq!(layers[2].pixels[0][1].rgba(), BLUE);
+    assert_eq!(mabel.pixels()[0][0].rgba(), RED);
+
+    // and back, with the groups restored
+    let ase = AsepriteFile::read(&mabel.to_aseprite().unwrap()[..]).unwrap();
+    let layers = ase
+        .layers()
+        .map(|l| {
+            let group = l.layer_type() == LayerType::Group;
+            (l.name().to_owned(), group, l.parent().map(|p| p.id()))
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        layers,
+        [
+            ("Background".to_owned(), false, None),
+            ("Characters".to_owned(), true, None),
+            ("Hero".to_owned(), false, Some(1)),
+            ("Hidden".to_owned(), true, Some(1)),
+            ("Ghost".to_owned(), false, Some(3)),
+        ]
+    );
+    assert_eq!(ase.layer(2).opacity(), 128);
+    assert_eq!(ase.layer(2).blend_mode(), BlendMode::Multiply);
+    assert!(!ase.layer(4).is_visible());
+    assert_eq!(ase.frame(0).layer(4).image().get_pixel(1, 0).0, BLUE);
+}
+
+/// Checks that the first frame of a document is drawn like `expected`.
+fn assert_drawn_like(mabel: &Mabel, expected: &RgbaImage, case: &str) {
+    let pixels = mabel.pixels();
+    for (x, y, expected) in expected.enumerate_pixels() {
+        let actual = pixels
+            .get(y as usize)
+            .and_then(|l| l.get(x as usize))
+            .map_or([0, 0, 0, 0], crate::Pixel::rgba);
+
+        if expected[3] == 0 {
+            assert_eq!(actual[3], 0, "{case} at {x}, {y}");
+        } else {
+            assert_eq!(actual, expected.0, "{case} at {x}, {y}");
+        }
+    }
+}
+
+fn raw_layers(raw_layers: bool) -> ConvertOptions {
+    ConvertOptions {
+        frames: Frames::Range(0, 0),
+        raw_layers,
+        ..ConvertOptions::default()
+    }
+}
+
+#[test]
+fn test_aseprite_blend() {
+    // a half transparent green multiplied over red and blue, in a cel at half opacity
+    let mut ase = AsepriteWriter::new(2, 1);
+    let mut top = NewLayer::new("Top");
+    top.blend_mode = BlendMode::Multiply;
+    top.opacity = 200;
+    ase.layers = vec![NewLayer::new("Bottom"), top];
+
+    let mut frame = NewFrame::new(100);
+    let bottom = RgbaImage::from_fn(2, 1, |x, _| Rgba(if x == 0 { RED } else { BLUE }));
+    frame.cels.push(NewCel::image(0, bottom));
+    let mut green = NewCel::image(1, RgbaImage::from_pixel(2, 1, Rgba([0, 255, 0, 255])));
+    green.opacity = 128;
+    frame.cels.push(green);
+    ase.frames.push(frame);
+    let ase = ase_file(ase);
+
+    // multiplied to black, then mixed in at 200 * 128 / 255 opacity
+    let flat = convert(&ase, &raw_layers(false));
+    assert_eq!(flat.pixels()[0][0].rgba(), [155, 0, 0, 255]);
+    assert_eq!(flat.pixels()[0][1].rgba(), [0, 0, 155, 255]);
+    // raw layers have the top pixels replace the bottom ones
+    let raw = convert(&ase, &raw_layers(true));
+    assert_eq!(raw.pixels()[0][0].rgba(), [0, 255, 0, 255]);
+    assert_eq!(raw.pixels()[0][1].rgba(), [0, 255, 0, 255]);
+}
+
+#[test]
+fn test_aseprite_cel_opacity() {
+    // translucent cels on a translucent layer, the second frame linked to the first
+    let mut ase = AsepriteWriter::new(2, 1);
+    let mut top = NewLayer::new("Top");
+    top.opacity = 200;
+    ase.layers = vec![NewLayer::new("Bottom"), top];
+    for i in 0..2 {
+        let mut frame = NewFrame::new(100);
+        let bottom = RgbaImage::from_fn(2, 1, |x, _| Rgba(if x == 0 { RED } else { BLUE }));
+        frame.cels.push(NewCel::image(0, bottom));
+        frame.cels.push(match i {
+            0 => {
+                let mut cel = NewCel::image(1, RgbaImage::from_pixel(2, 1, Rgba([0, 255, 0, 180])));
+                cel.opacity = 128;
+                cel
+            }
+            _ => NewCel::linked(1, 0),
+        });
+        ase.frames.push(frame);
+    }
+    let ase = ase_file(ase);
+
+    let layers = ConvertOptions {
+        layers: true,
+        ..ConvertOptions::default()
+    };
+    let layered = convert(&ase, &layers);
+    let flat = convert(&ase, &ConvertOptions::default());
+    let rgba = |frame: &crate::Frame| {
+        frame.pixels()[0]
+            .iter()
+            .map(Pixel::rgba)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(layered.frames.len(), 2);
+    for (layered, flat) in layered.frames.iter().zip(&flat.frames) {
+        // 200 * 128 / 255
+        assert_eq!(layered.layers[1].opacity, 100);
+        assert_eq!(rgba(layered), rgba(flat));
+    }
+}
+
+#[test]
+fn test_aseprite_blend_modes() {
+    // the layers of aseprite's blend reference images, drawn into the
+    // blend_{mode}.png files by aseprite itself. a corner of each is enough
+    // to cover every kind of pixel without converting all of them.
+    let data = concat!(env!("CARGO_MANIFEST_DIR"), "/../mabel-aseprite/tests/data");
+    let read = |name: &str| {
+        let path = format!("{data}/{name}.png");
+        let image = crate::import::read_png(&path).unwrap_or_else(|e| panic!("{path}: {e}"));
+        image::imageops::crop_imm(&image, 0, 0, 64, 64).to_image()
+    };
+    let bottom = read("blend_bottom");
+    let top = read("blend_top");
+    let modes = [
+        (BlendMode::Normal, "normal"),
+        (BlendMode::Multiply, "multiply"),
+        (BlendMode::Screen, "screen"),
+        (BlendMode::Overlay, "overlay"),
+        (BlendMode::Darken, "darken"),
+        (BlendMode::Lighten, "lighten"),
+        (BlendMode::ColorDodge, "colordodge"),
+        (BlendMode::ColorBurn, "colorburn"),
+        (BlendMode::HardLight, "hardlight"),
+        (BlendMode::SoftLight, "softlight"),
+        (BlendMode::Difference, "difference"),
+        (BlendMode::Exclusion, "exclusion"),
+        (BlendMode::Hue, "hue"),
+        (BlendMode::Saturation, "saturation"),
+        (BlendMode::Color, "color"),
+        (BlendMode::Luminosity, "luminosity"),
+        (BlendMode::Addition, "addition"),
+        (BlendMode::Subtract, "subtract"),
+        (BlendMode::Divide, "divide"),
+    ];
+
+    for (mode, name) in modes {
+        let mut ase = AsepriteWriter::new(bottom.width() as u16, bottom.height() as u16);
+        let mut layer = NewLayer::new("Top");
+        layer.blend_mode = mode;
+        ase.layers = vec![NewLayer::new("Bottom"), layer];
+        let mut frame = NewFrame::new(100);
+        frame.cels.push(NewCel::image(0, bottom.clone()));
+        frame.cels.push(NewCel::image(1, top.clone()));
+        ase.frames.push(frame);
+        let ase = ase_file(ase);
+
+        let options = ConvertOptions {
+            layers: true,
+            ..ConvertOptions::default()
+        };
+        let mabel = convert(&ase, &options);
+        assert_eq!(mabel.frames[0].layers[1].blend_mode, mode);
+        assert_drawn_like(&mabel, &read(&format!("blend_{name}")), name);
+    }
+}
+
+#[test]
+fn test_aseprite_indexed() {
+    // an indexed file with named colors, the second frame linked to the first
+    let mut ase = AsepriteWriter::new(2, 1);
+    ase.pixel_format = PixelFormat::Indexed {
+        transparent_color_index: 0,
+    };
+    let named = |rgba, name: &str| NewColor {
+        rgba,
+        name: Some(name.to_owned()),
+    };
+    ase.palette = vec![
+        [0, 0, 0, 0].into(),
+        named(BLUE, "sky"),
+        [0, 255, 0, 255].into(),
+        named(RED, "r"),
+    ];
+    ase.layers.push(NewLayer::new("Layer 1"));
+    let mut frame = NewFrame::new(100);
+    let image = RgbaImage::from_fn(2, 1, |x, _| Rgba(if x == 0 { RED } else { BLUE }));
+    frame.cels.push(NewCel::image(0, image));
+    ase.frames.push(frame);
+    let mut frame = NewFrame::new(100);
+    frame.cels.push(NewCel::linked(0, 0));
+    ase.frames.push(frame);
+    let ase = ase_file(ase);
+
+    for raw_layers in [false, true] {
+        let options = ConvertOptions {
+            raw_layers,
+            ..ConvertOptions::default()
+        };
+        let eno = &to_eno(&ase, &options).unwrap()[0];
+        let mabel = mabel(eno).unwrap();
+
+        // in index order, with names that fit as keys
+        let palette = mabel
+            .palette
+            .iter()
+            .map(|(k, p)| (k.as_str(), p.rgba()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            palette,
+            [
+                ("0", BLUE),
+                ("1", [0, 255, 0, 255]),
+                ("r", RED),
+                (" ", [0, 0, 0, 0])
+            ]
+        );
+        assert!(eno.contains("> sky\n0 = "));
+        assert_eq!(first_pixels(&mabel), [RED, RED]);
+        assert_eq!(mabel.frames[1].pixels()[0][1].rgba(), BLUE);
+    }
+}
+
+#[test]
+fn test_aseprite_grayscale() {
+    let mut ase = AsepriteWriter::new(3, 1);
+    ase.pixel_format = PixelFormat::Grayscale;
+    ase.layers.push(NewLayer::new("Layer 1"));
+    let grays = [[0, 0, 0, 255], [128, 128, 128, 100], [0, 0, 0, 0]];
+    let mut frame = NewFrame::new(100);
+    frame.cels.push(NewCel::image(
+        0,
+        RgbaImage::from_fn(3, 1, |x, _| Rgba(grays[x as usize])),
+    ));
+    ase.frames.push(frame);
+    let ase = ase_file(ase);
+
+    for raw_layers in [false, true] {
+        let options = ConvertOptions {
+            raw_layers,
+            ..ConvertOptions::default()
+        };
+        let mabel = convert(&ase, &options);
+        let pixels = mabel.pixels()[0]
+            .iter()
+            .map(Pixel::rgba)
+            .collect::<Vec<_>>();
+        assert_eq!(pixels, grays);
+    }
+}
+
+#[test]
+fn test_aseprite_tilemap() {
+    // a half opaque tilemap of red and blue tiles over a green background
+    let mut ase = AsepriteWriter::new(4, 2);
+    let mut tileset = NewTileset::new("Tiles", 2, 2);
+    tileset.tiles = vec![
+        RgbaImage::from_pixel(2, 2, Rgba(RED)),
+        RgbaImage::from_pixel(2, 2, Rgba(BLUE)),
+    ];
+    ase.tilesets.push(tileset);
+    let mut tilemap = NewLayer::new("Tilemap");
+    tilemap.tileset = Some(0);
+    tilemap.opacity = 128;
+    ase.layers = vec![NewLayer::new("Background"), tilemap];
+    let mut frame = NewFrame::new(100);
+    let green = RgbaImage::from_pixel(4, 2, Rgba([0, 255, 0, 255]));
+    frame.cels.push(NewCel::image(0, green));
+    frame.cels.push(NewCel::tilemap(1, 2, vec![1, 2]));
+    ase.frames.push(frame);
+    let ase = ase_file(ase);
+
+    let expected = ase.frame(0).image();
+    assert_eq!(expected.get_pixel(0, 0).0, [128, 127, 0, 255]);
+    assert_drawn_like(&convert(&ase, &raw_layers(false)), &expected, "flat");
+
+    // the tiles are drawn with the layer opacity, so the layer is opaque
+    let options = ConvertOptions {
+        layers: true,
+        ..ConvertOptions::default()
+    };
+    let mabel = convert(&ase, &options);
+    let layer = &mabel.frames[0].layers[1];
+    assert_eq!(layer.name, "Tilemap");
+    assert_eq!(layer.opacity, 255);
+    assert_eq!(layer.pixels[1][3].rgba(), [0, 0, 255, 128]);
+    assert_drawn_like(&mabel, &expected, "layers");
+
+    // raw layers have the tiles replace the background
+    let raw = convert(&ase, &raw_layers(true));
+    assert_eq!(raw.pixels()[0][0].rgba(), [255, 0, 0, 128]);
+}
+
+/// The colors of the image [`convert_keys`] converts, from left to right.
+const COLORS: [[u8; 4]; 5] = [
+    RED,
+    [255, 255, 255, 255],
+    [0, 0, 0, 255],
+    [128, 0, 0, 255],
+    [224, 0, 0, 255],
+];
+
+fn convert_keys(options: &KeyOptions) -> crate::Result<String> {
+    let mut ase = AsepriteWriter::new(5, 1);
+    ase.layers.push(NewLayer::new("Layer 1"));
+    let mut frame = NewFrame::new(100);
+    frame.cels.push(NewCel::image(
+        0,
+        RgbaImage::from_fn(5, 1, |x, _| Rgba(COLORS[x as usize])),
+    ));
+    ase.frames.push(frame);
+
+    let options = ConvertOptions {
+        keys: options.clone(),
+        ..ConvertOptions::default()
+    };
+    Ok(to_eno(&ase_file(ase), &options)?.concat())
+}
+
+fn pixels_line(eno: &str) -> &str {
+    eno.lines()
+        .skip_while(|l| *l != "-- pixels")
+        .nth(1)
+        .unwrap()
+}
+
+#[test]
+fn test_aseprite_key_order() {
+    let options = KeyOptions::default();
+    let eno = convert_keys(&options).unwrap();
+    assert_eq!(pixels_line(&eno), "01234");
+    // the same file always gets the same keys
+    assert_eq!(eno, convert_keys(&options).unwrap());
+
+    let options = KeyOptions {
+        order: "luminance".parse().unwrap(),
+        ..KeyOptions::default()
+    };
+    let eno = convert_keys(&options).unwrap();
+    assert_eq!(pixels_line(&eno), "34012");
+    assert!("brightness".parse::<Order>().is_err());
+
+    // an indexed file whose colors appear out of index order, one unused
+    let mut ase = AsepriteWriter::new(4, 1);
+    ase.pixel_format = PixelFormat::Indexed {
+        transparent_color_index: 0,
+    };
+    let white = [255, 255, 255, 255];
+    ase.palette = [[0, 0, 0, 0], BLUE, COLORS[2], RED, white]
+        .map(NewColor::from)
+        .to_vec();
+    ase.layers.push(NewLayer::new("Layer 1"));
+    let mut frame = NewFrame::new(100);
+    let order = [RED, BLUE, RED, COLORS[2]];
+    let image = RgbaImage::from_fn(4, 1, |x, _| Rgba(order[x as usize]));
+    frame.cels.push(NewCel::image(0, image));
+    ase.frames.push(frame);
+    let ase = ase_file(ase);
+
+    let cases = [
+        (Order::Palette, "2021", [BLUE, COLORS[2], RED, white]),
+        (Order::Appearance, "0102", [RED, BLUE, COLORS[2], white]),
+    ];
+    for (order, pixels, palette) in cases {
+        let options = ConvertOptions {
+            keys: KeyOptions {
+                order,
+                ..KeyOptions::default()
+            },
+            ..ConvertOptions::default()
+        };
+        let eno = &to_eno(&ase, &options).unwrap()[0];
+        assert_eq!(pixels_line(eno), pixels, "{order:?}");
+        let mabel = mabel(eno).unwrap();
+        let colors = mabel.palette.values().map(Pixel::rgba);
+        assert_eq!(colors.take(4).collect::<Vec<_>>(), palette, "{order:?}");
+    }
+}
+
+#[test]
+fn test_aseprite_smart_keys() {
+    let options = KeyOptions {
+        smart: true,
+        ..KeyOptions::default()
+    };
+    let eno = convert_keys(&options).unwrap();
+    // the second red falls back to the first free key
+    assert_eq!(pixels_line(&eno), "rwkR0");
+}
+
+#[test]
+fn test_aseprite_key_alphabet() {
+    let alphabet = |keys: &str| KeyOptions {
+        alphabet: Some(keys.to_owned()),
+        ..KeyOptions::default()
+    };
+
+    let eno = convert_keys(&alphabet("xyz")).unwrap();
+    assert!(eno.contains("key_width: 2\n"));
+    assert_eq!(pixels_line(&eno), "xxxyxzyxyy");
+    mabel(&eno).unwrap();
+
+    // keys narrower than the widest one are padded to line up
+    let eno = convert_keys(&alphabet("🍁a")).unwrap();
+    assert_eq!(pixels_line(&eno), "🍁🍁🍁🍁🍁a 🍁a🍁 🍁aa  a🍁🍁 ");
+    let pixels = mabel(&eno).unwrap().pixels();
+    assert_eq!(
+        pixels[0].iter().map(Pixel::rgba).collect::<Vec<_>>(),
+        COLORS
+    );
+
+    let error = |keys| convert_keys(&alphabet(keys)).unwrap_err().to_string();
+    assert_eq!(error("ab a"), "Invalid key \" \" in the key alphabet");
+    assert_eq!(error("aba"), "Duplicate key \"a\" in the key alphabet");
+    assert_eq!(error(""), "The key alphabet is empty");
+}
+
+#[test]
+fn test_aseprite_convert_options() {
+    // red and blue in the middle of a transparent 6x4 canvas, moving down a row
+    let mut ase = AsepriteWriter::new(6, 4);
+    ase.layers.push(NewLayer::new("Layer 1"));
+    for row in [1, 2] {
+        let image = RgbaImage::from_fn(6, 4, |x, y| match (x, y) {
+            (2, y) if y == row => Rgba(RED),
+            (3, y) if y == row => Rgba(BLUE),
+            _ => Rgba([0, 0, 0, 0]),
+        });
+        let mut frame = NewFrame::new(100);
+        frame.cels.push(NewCel::image(0, image));
+        ase.frames.push(frame);
+    }
+
+    let options = ConvertOptions {
+        size: PixelSize::Width(10),
+        notation: Notation::Rgb,
+        dimensions: true,
+        trim: true,
+        ..ConvertOptions::default()
+    };
+    let documents = to_eno(&ase_file(ase), &options).unwrap();
+    assert_eq!(documents.len(), 1);
+
+    let eno = &documents[0];
+    // trimmed to the rows both frames use, then scaled to fit 10 pixels
+    assert!(eno.starts_with("size: 5\nwidth: 2\nheight: 2\n"));
+    assert!(eno.contains("= rgb(255, 0, 0)\n"));
+    let mabel = mabel(eno).unwrap();
+    assert_eq!(mabel.image_width(), 10);
+    assert_eq!(mabel.frames[1].pixels()[1][1].rgba(), BLUE);
+}
+
+#[test]
+fn test_aseprite_alpha() {
+    let alphas = [1, 51, 100, 128, 254];
+    let mut ase = AsepriteWriter::new(alphas.len() as u16, 1);
+    ase.layers.push(NewLayer::new("Layer 1"));
+    let image = RgbaImage::from_fn(alphas.len() as u32, 1, |x, _| {
+        Rgba([200, 17, 3, alphas[x as usize]])
+    });
+    let mut frame = NewFrame::new(100);
+    frame.cels.push(NewCel::image(0, image));
+    ase.frames.push(frame);
+    let ase = ase_file(ase);
+
+    // every alpha reads back as it was, in every notation
+    for notation in [Notation::Hex, Notation::Rgb, Notation::Hsl, Notation::Named] {
+        let options = ConvertOptions {
+            notation,
+            ..ConvertOptions::default()
+        };
+        let mabel = mabel(&to_eno(&ase, &options).unwrap()[0]).unwrap();
+        let pixels = mabel.pixels()[0]
+            .iter()
+            .map(Pixel::rgba)
+            .collect::<Vec<_>>();
+        assert_eq!(pixels, alphas.map(|a| [200, 17, 3, a]), "{notation:?}");
+    }
+}
+
+#[test]
+fn test_aseprite_metadata() {
+    let user_data = |text: &str, color: Option<[u8; 4]>| {
+        Some(UserData {
+            text: Some(text.to_owned()),
+            color: color.map(Rgba),
+        })
+    };
+    let mut ase = AsepriteWriter::new(4, 4);
+    ase.user_data = user_data("made with\nmabel", Some(RED));
+    let mut layer = NewLayer::new("Layer 1");
+    layer.user_data = user_data("outline", None);
+    ase.layers.push(layer);
+    for i in 0..3 {
+        let image = RgbaImage::from_fn(4, 4, |x, y| match (x, y) {
+            (1..=2, 1..=2) => Rgba(BLUE),
+            _ => Rgba([0, 0, 0, 0]),
+        });
+        let mut frame = NewFrame::new(100);
+        let mut cel = NewCel::image(0, image);
+        if i == 0 {
+            cel.user_data = user_data("first", None);
+        }
+        frame.cels.push(cel);
+        ase.frames.push(frame);
+    }
+    let mut tag = NewTag::new("walk", 1, 2);
+    tag.animation_direction = AnimationDirection::PingPong;
+    tag.repeat = 2;
+    tag.user_data = user_data("steps", None);
+    ase.tags.push(tag);
+    let key = |from_frame, x| SliceKey {
+        from_frame,
+        origin: (x, 1),
+        size: (2, 2),
+        slice9: Some(Slice9 {
+            center_x: 0,
+            center_y: 0,
+            center_width: 1,
+            center_height: 1,
+        }),
+        pivot: Some((1, 1)),
+    };
+    ase.slices.push(Slice {
+        name: "body".to_owned(),
+        keys: vec![key(0, 1), key(2, 2)],
+        user_data: None,
+    });
+    let ase = ase_file(ase);
+
+    let options = ConvertOptions {
+        layers: true,
+        frames: Frames::Range(1, 2),
+        trim: true,
+        ..ConvertOptions::default()
+    };
+    let eno = &to_eno(&ase, &options).unwrap()[0];
+    assert!(eno.contains("-- user_data\nmade with\nmabel\n-- user_data\nuser_color: #ff0000\n"));
+    assert!(eno.contains("name: Layer 1\nuser_data: outline\n"));
+    // the tag's frames are counted from the first converted frame
+    assert!(eno.contains(indoc! {"
+        # tag
+        name: walk
+        from: 0
+        to: 1
+        direction: ping-pong
+        repeat: 2
+        user_data: steps
+    "}));
+    // slices move with the trimmed image, with a section for each key
+    assert!(eno.ends_with(indoc! {"
+        # slice
+        name: body
+        frame: 0
+        x: 0
+        y: 0
+        width: 2
+        height: 2
+        center: 0, 0, 1, 1
+        pivot: 1, 1
+
+        # slice
+        name: body
+        frame: 1
+        x: 1
+        y: 0
+        width: 2
+        height: 2
+        center: 0, 0, 1, 1
+        pivot: 1, 1
+    "}));
+    // metadata isn't drawn
+    let mabel = mabel(eno).unwrap();
+    assert_eq!(mabel.frames.len(), 2);
+    assert_eq!(mabel.frames[0].pixels()[0][0].rgba(), BLUE);
+
+    // the tag doesn't cover the first frame
+    let options = ConvertOptions {
+        layers: true,
+        frames: Frames::Range(0, 0),
+        ..ConvertOptions::default()
+    };
+    let eno = &to_eno(&ase, &options).unwrap()[0];
+    assert!(eno.contains("user_data: outline\ncel_data: first\n"));
+    assert!(!eno.contains("# tag"));
+    assert!(eno.contains("# slice\nname: body\nx: 1\ny: 1\n"));
+}
+
+#[test]
+fn test_aseprite_drawn_user_data() {
+    let user_data = |text: &str| {
+        Some(UserData {
+            text: Some(text.to_owned()),
+            color: None,
+        })
+    };
+    let mut ase = AsepriteWriter::new(2, 1);
+    let mut layer = NewLayer::new("Body");
+    layer.user_data = user_data("outline");
+    ase.layers = vec![layer, NewLayer::new("Eyes")];
+    for i in 0..2 {
+        let mut frame = NewFrame::new(100);
+        frame
+            .cels
+            .push(NewCel::image(0, RgbaImage::from_pixel(2, 1, Rgba(RED))));
+        let mut cel = NewCel::image(1, RgbaImage::from_pixel(1, 1, Rgba(BLUE)));
+        cel.user_data = user_data(&format!("blink {i}"));
+        frame.cels.push(cel);
+        ase.frames.push(frame);
+    }
+    let ase = ase_file(ase);
+
+    // without layer sections, layers and cels get sections of their own
+    let eno = &to_eno(&ase, &ConvertOptions::default()).unwrap()[0];
+    assert!(eno.contains(indoc! {"
+        # layer_data
+        name: Body
+        user_data: outline
+
+        # cel_data
+        layer: Eyes
+        frame: 0
+        cel_data: blink 0
+
+        # cel_data
+        layer: Eyes
+        frame: 1
+        cel_data: blink 1
+    "}));
+    let mabel = mabel(eno).unwrap();
+    assert_eq!(mabel.frames.len(), 2);
+    assert_eq!(mabel.frames[1].pixels()[0][0].rgba(), BLUE);
+
+    // a flat document has the cels of its own frame
+    let options = ConvertOptions {
+        flat: true,
+        ..ConvertOptions::default()
+    };
+    let documents = to_eno(&ase, &options).unwrap();
+    assert!(documents[1].ends_with(indoc! {"
+        # layer_data
+        name: Body
+        user_data: outline
+
+        # cel_data
+        layer: Eyes
+        cel_data: blink 1
+    "}));
+}
+
+#[test]
+fn test_aseprite_single_frame_tag() {
+    let mut ase = AsepriteWriter::new(1, 1);
+    ase.layers.push(NewLayer::new("Layer 1"));
+    let mut frame = NewFrame::new(100);
+    frame
+        .cels
+        .push(NewCel::image(0, RgbaImage::from_pixel(1, 1, Rgba(RED))));
+    ase.frames.push(frame);
+    ase.tags.push(NewTag::new("idle", 0, 0));
+    let ase = ase_file(ase);
+
+    let eno = &to_eno(&ase, &ConvertOptions::default()).unwrap()[0];
+    assert!(eno.ends_with(indoc! {"
+        # tag
+        name: idle
+        from: 0
+        to: 0
+        direction: forward
+    "}));
+    assert_eq!(mabel(eno).unwrap().frames.len(), 1);
+}
+
+fn ase_file(ase: AsepriteWriter) -> AsepriteFile {
+    let mut data = vec![];
+    ase.write(&mut data).unwrap();
+    AsepriteFile::read(&data[..]).unwrap()
+}
@@ -0,0 +1,71 @@
+#[cfg(any(feature = "qoi", feature = "bmp", feature = "tga", feature = "webp"))]
+use indoc::indoc;
+
+use crate::format::Format;
+#[cfg(any(feature = "qoi", feature = "bmp", feature = "tga", feature = "webp"))]
+use crate::Mabel;
+
+#[cfg(any(feature = "qoi", feature = "bmp", feature = "tga", feature = "webp"))]
+const DOCUMENT: &str = indoc! {"
+    size: 2
+    palette:
+    r = #ff0000
+    b = #0000ff
+
+    -- pixels
+    rb
+     r
+    -- pixels
+"};
+
+/// Writes the document in `format` and decodes it back to rgba.
+#[cfg(any(feature = "qoi", feature = "bmp", feature = "tga", feature = "webp"))]
+fn round_trip(format: Format, image_format: image::ImageFormat) {
+    let mabel = Mabel::from(&mabel_eno::parse(DOCUMENT).unwrap()).unwrap();
+    let path = std::env::temp_dir().join(format!("mabel-test-format.{}", format.extension()));
+    mabel.save(path.to_str().unwrap(), format).unwrap();
+
+    let data = std::fs::read(&path).unwrap();
+    let image = image::load_from_memory_with_format(&data, image_format).unwrap();
+    assert_eq!((image.width(), image.height()), (4, 4));
+
+    assert_eq!(
+        image.to_rgba8().into_raw(),
+        mabel.frame_rgba(&mabel.frames[0])
+    );
+}
+
+#[test]
+fn test_format_from_path() {
+    assert_eq!(Format::from_path("out").unwrap(), Format::Png);
+    assert_eq!(Format::from_path("out.GIF").unwrap(), Format::Gif);
+    assert_eq!(Format::from_path("a.b/out.svg").unwrap(), Format::Svg);
+    assert_eq!(
+        Format::from_path("out.jpg").unwrap_err().to_string(),
+        "Unknown format \"jpg\", must be png, gif, svg, ico, cur, qoi, bmp, tga or webp"
+    );
+}
+
+#[cfg(feature = "qoi")]
+#[test]
+fn test_format_qoi() {
+    round_trip(Format::Qoi, image::ImageFormat::Qoi);
+}
+
+#[cfg(feature = "bmp")]
+#[test]
+fn test_format_bmp() {
+    round_trip(Format::Bmp, image::ImageFormat::Bmp);
+}
+
+#[cfg(feature = "tga")]
+#[test]
+fn test_format_tga() {
+    round_trip(Format::Tga, image::ImageFormat::Tga);
+}
+
+#[cfg(feature = "webp")]
+#[test]
+fn test_format_webp() {
+    round_trip(Format::WebP, image::ImageFormat::WebP);
+}
@@ -0,0 +1,64 @@
+use indoc::indoc;
+
+use crate::tests::mabel;
+
+fn u16_at(data: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([data[i], data[i + 1]])
+}
+
+fn u32_at(data: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes(data[i..i + 4].try_into().unwrap())
+}
+
+const ARROW: &str = indoc! {"
+    hotspot: 1, 0
+    palette:
+    b = #000000
+
+    -- pixels
+    bb
+    b
+    -- pixels
+"};
+
+#[test]
+fn test_ico_sizes() {
+    let ico = mabel(ARROW).unwrap().to_ico(false).unwrap();
+
+    assert_eq!(u16_at(&ico, 2), 1);
+    assert_eq!(u16_at(&ico, 4), 4);
+
+    let sizes = (0..4).map(|i| ico[6 + i * 16]).collect::<Vec<_>>();
+    assert_eq!(sizes, [16, 32, 48, 0]);
+
+    // the 16px bitmap is 2x2 blocks of 8, its bottom-right block is transparent
+    let offset = u32_at(&ico, 6 + 12) as usize;
+    assert_eq!(u32_at(&ico, offset + 8), 32);
+    let mask = &ico[offset + 40 + 16 * 16 * 4..];
+    assert_eq!(mask[..4], [0b0000_0000, 0b1111_1111, 0, 0]);
+    assert_eq!(mask[4 * 15..4 * 16], [0, 0, 0, 0]);
+
+    // the 256px rendering is a png
+    let offset = u32_at(&ico, 6 + 3 * 16 + 12) as usize;
+    assert_eq!(ico[offset..offset + 8], *b"\x89PNG\r\n\x1a\n");
+}
+
+#[test]
+fn test_cur_hotspot() {
+    let cur = mabel(ARROW).unwrap().to_ico(true).unwrap();
+
+    assert_eq!(u16_at(&cur, 2), 2);
+    let hotspots = (0..4)
+        .map(|i| (u16_at(&cur, 6 + i * 16 + 4), u16_at(&cur, 6 + i * 16 + 6)))
+        .collect::<Vec<_>>();
+    assert_eq!(hotspots, [(8, 0), (16, 0), (24, 0), (128, 0)]);
+}
+
+#[test]
+fn test_hotspot_outside() {
+    let err = mabel(&ARROW.replace("hotspot: 1, 0", "hotspot: 2, 0")).unwrap_err();
+    assert_eq!(err.to_string(), "Hotspot 2, 0 is outside the image");
+
+    let err = mabel(&ARROW.replace("hotspot: 1, 0", "hotspot: 1")).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid hotspot \"1\", must be x, y");
+}
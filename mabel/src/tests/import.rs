@@ -0,0 +1,70 @@
+use image::{Rgba, RgbaImage};
+
+use crate::{import::to_eno, tests::mabel};
+
+const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+const CLEAR: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+/// A 2x2 image of red, blue, nothing and red, scaled up by `size`.
+fn checker(size: u32) -> RgbaImage {
+    RgbaImage::from_fn(2 * size, 2 * size, |x, y| match (x / size, y / size) {
+        (0, 0) | (1, 1) => RED,
+        (1, 0) => BLUE,
+        _ => CLEAR,
+    })
+}
+
+#[test]
+fn test_import_scale() {
+    let eno = to_eno(&checker(3), None).unwrap();
+
+    assert_eq!(
+        eno,
+        "size: 3\npalette:\n0 = #ff0000\n1 = #0000ff\n\n-- pixels\n01\n 0\n-- pixels\n"
+    );
+}
+
+#[test]
+fn test_import_round_trip() {
+    let mut image = checker(4);
+    image.put_pixel(7, 7, Rgba([0, 255, 0, 51]));
+    // alpha steps that two decimals can't tell apart
+    image.put_pixel(6, 7, Rgba([0, 255, 0, 100]));
+    image.put_pixel(7, 6, Rgba([0, 255, 0, 1]));
+
+    let mabel = mabel(&to_eno(&image, None).unwrap()).unwrap();
+    assert_eq!(mabel.size, 1);
+    assert_eq!(mabel.frame_rgba(&mabel.frames[0]), image.into_raw());
+}
+
+#[test]
+fn test_import_key_width() {
+    let image = RgbaImage::from_fn(100, 1, |x, _| Rgba([x as u8, 0, 0, 255]));
+    let eno = to_eno(&image, None).unwrap();
+
+    assert!(eno.starts_with("size: 1\nkey_width: 2\npalette:\n00 = #000000\n"));
+    let mabel = mabel(&eno).unwrap();
+    assert_eq!(mabel.frame_rgba(&mabel.frames[0]), image.into_raw());
+}
+
+#[test]
+fn test_import_max_colors() {
+    let image = RgbaImage::from_fn(4, 1, |x, _| match x {
+        0 => Rgba([250, 0, 0, 255]),
+        1 => Rgba([254, 0, 0, 255]),
+        2 => Rgba([0, 0, 250, 255]),
+        _ => Rgba([0, 0, 254, 255]),
+    });
+    let eno = to_eno(&image, Some(2)).unwrap();
+
+    assert_eq!(
+        eno,
+        "size: 1\npalette:\n0 = #fc0000\n1 = #0000fc\n\n-- pixels\n0011\n-- pixels\n"
+    );
+}
+
+#[test]
+fn test_import_max_colors_zero() {
+    assert!(to_eno(&checker(1), Some(0)).is_err());
+}
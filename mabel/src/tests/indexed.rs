@@ -0,0 +1,86 @@
+use indoc::indoc;
+
+use crate::{tests::mabel, Mabel};
+
+/// Writes the document as an indexed png and reads back its header info and
+/// first frame.
+fn decode(mabel: &Mabel, name: &str) -> (png::Info<'static>, Vec<u8>) {
+    let path = std::env::temp_dir().join(name);
+    mabel.save_indexed_png(path.to_str().unwrap()).unwrap();
+
+    let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut data = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut data).unwrap();
+    data.truncate(frame.buffer_size());
+
+    (reader.info().clone(), data)
+}
+
+#[test]
+fn test_indexed_declaration_order() {
+    let mabel = mabel(indoc! {"
+        palette:
+        z = #0000ff
+        a = #ff0000
+
+        -- pixels
+        az
+        z a
+        -- pixels
+    "})
+    .unwrap();
+
+    assert_eq!(
+        mabel.indexed_colors(),
+        [[0, 0, 255, 255], [255, 0, 0, 255], [0, 0, 0, 0]]
+    );
+
+    let (info, data) = decode(&mabel, "mabel-test-indexed-order.png");
+    assert_eq!(info.color_type, png::ColorType::Indexed);
+    assert_eq!(info.bit_depth, png::BitDepth::Two);
+    assert_eq!(
+        info.palette.as_deref(),
+        Some(&[0, 0, 255, 255, 0, 0, 0, 0, 0][..])
+    );
+    assert_eq!(info.trns.as_deref(), Some(&[255, 255, 0][..]));
+    // 2 bits per pixel, rows padded to a byte
+    assert_eq!(data, [0b0100_1000, 0b0010_0100]);
+}
+
+#[test]
+fn test_indexed_bit_depth() {
+    let palette = (0..17)
+        .map(|i| format!("{} = rgb({i}, 0, 0)\n", crate::palette::key(i, 1)))
+        .collect::<String>();
+    let mabel = mabel(&format!("palette:\n{palette}\n-- pixels\n0g\n-- pixels\n")).unwrap();
+
+    let (info, data) = decode(&mabel, "mabel-test-indexed-depth.png");
+    assert_eq!(info.bit_depth, png::BitDepth::Eight);
+    // the transparent entry comes last, after the 17 opaque colors
+    let mut trns = vec![255; 17];
+    trns.push(0);
+    assert_eq!(info.trns.as_deref(), Some(trns.as_slice()));
+    assert_eq!(data, [0, 16]);
+}
+
+#[test]
+fn test_indexed_rgba_fallback() {
+    let palette = (0..300)
+        .map(|i| {
+            format!(
+                "{} = rgb({}, {}, 0)\n",
+                crate::palette::key(i, 2),
+                i % 256,
+                i / 256
+            )
+        })
+        .collect::<String>();
+    let mabel = mabel(&format!(
+        "key_width: 2\npalette:\n{palette}\n-- pixels\n00\n-- pixels\n"
+    ))
+    .unwrap();
+
+    let (info, _) = decode(&mabel, "mabel-test-indexed-fallback.png");
+    assert_eq!(info.color_type, png::ColorType::Rgba);
+}
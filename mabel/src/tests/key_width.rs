@@ -0,0 +1,116 @@
+use indoc::indoc;
+
+use crate::palette::{key, key_width};
+use crate::tests::{mabel, rgba};
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+const TRANS: [u8; 4] = [0, 0, 0, 0];
+
+#[test]
+fn test_two_character_keys() {
+    let mabel = mabel(indoc! {"
+        key_width: 2
+        palette:
+        r1 = #ff0000
+        r2 = #0000ff
+
+        -- pixels
+        r1r2  r1
+        r2r1
+        -- pixels
+    "})
+    .unwrap();
+
+    assert_eq!(rgba(&mabel), [vec![RED, BLUE, TRANS, RED], vec![BLUE, RED]]);
+    assert_eq!(mabel.pixels_width(), 4);
+}
+
+#[test]
+fn test_three_character_keys() {
+    let mabel = mabel(indoc! {"
+        key_width: 3
+        palette:
+        red = #ff0000
+        blu = #0000ff
+
+        -- pixels
+        redblu   red
+        -- pixels
+    "})
+    .unwrap();
+
+    assert_eq!(rgba(&mabel), [[RED, BLUE, TRANS, RED]]);
+}
+
+#[test]
+fn test_key_width_row_length() {
+    let error = mabel(indoc! {"
+        key_width: 2
+        palette:
+        rr = #ff0000
+
+        -- pixels
+        rrrr
+        rrr
+        -- pixels
+    "})
+    .unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Pixels line 2 is 3 characters long, which is not a multiple of the key width 2"
+    );
+}
+
+#[test]
+fn test_key_width_palette_keys() {
+    let error = mabel(indoc! {"
+        key_width: 3
+        palette:
+        rr = #ff0000
+
+        -- pixels
+        rr
+        -- pixels
+    "})
+    .unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Invalid palette key \"rr\", must be 3 characters long"
+    );
+}
+
+#[test]
+fn test_key_width_out_of_range() {
+    for width in ["0", "4", "two"] {
+        let error = mabel(&format!(
+            "key_width: {width}\npalette:\nr = #ff0000\n\n-- pixels\nr\n-- pixels\n"
+        ))
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            format!("Invalid key_width \"{width}\", must be 1, 2 or 3")
+        );
+    }
+}
+
+#[test]
+fn test_generated_keys() {
+    // 62 key characters fit 62 colors in one, 3844 in two
+    assert_eq!(key_width(62).unwrap(), 1);
+    assert_eq!(key_width(63).unwrap(), 2);
+    assert_eq!(key_width(3844).unwrap(), 2);
+    assert_eq!(key_width(3845).unwrap(), 3);
+    assert_eq!(
+        key_width(62 * 62 * 62 + 1).unwrap_err().to_string(),
+        "Too many colors for palette keys, found 238329 colors"
+    );
+
+    assert_eq!(key(0, 2), "00");
+    assert_eq!(key(61, 2), "0Z");
+    assert_eq!(key(62, 2), "10");
+    assert_eq!(key(63, 3), "011");
+}
@@ -0,0 +1,192 @@
+use color_art::Color;
+use indoc::indoc;
+
+use crate::{
+    layer::{composite, parse_blend_mode},
+    tests::mabel,
+    BlendMode, Layer, Pixel,
+};
+
+fn pixel([r, g, b, a]: [u8; 4]) -> Pixel {
+    Pixel::Colored(Color::new(r, g, b, f64::from(a) / 255.0))
+}
+
+/// A single `top` pixel drawn over a `bottom` one.
+fn composite_pixel(bottom: [u8; 4], top: [u8; 4], blend_mode: BlendMode, opacity: u8) -> [u8; 4] {
+    let mut layer = Layer::new("top", vec![vec![pixel(top)]]);
+    layer.blend_mode = blend_mode;
+    layer.opacity = opacity;
+
+    composite(&[Layer::new("bottom", vec![vec![pixel(bottom)]]), layer])[0][0].rgba()
+}
+
+#[test]
+fn test_blend_modes() {
+    let cases = [
+        (
+            BlendMode::Normal,
+            [0, 0, 255, 255],
+            [255, 0, 0, 255],
+            [255, 0, 0, 255],
+        ),
+        (
+            BlendMode::Multiply,
+            [128, 255, 255, 255],
+            [255, 128, 0, 255],
+            [128, 128, 0, 255],
+        ),
+        (
+            BlendMode::Screen,
+            [0, 128, 0, 255],
+            [128, 0, 0, 255],
+            [128, 128, 0, 255],
+        ),
+        (
+            BlendMode::Darken,
+            [100, 200, 50, 255],
+            [150, 100, 50, 255],
+            [100, 100, 50, 255],
+        ),
+        (
+            BlendMode::Lighten,
+            [100, 200, 50, 255],
+            [150, 100, 50, 255],
+            [150, 200, 50, 255],
+        ),
+        (
+            BlendMode::Difference,
+            [100, 100, 100, 255],
+            [200, 50, 0, 255],
+            [100, 50, 100, 255],
+        ),
+        (
+            BlendMode::Addition,
+            [100, 100, 100, 255],
+            [200, 100, 0, 255],
+            [255, 200, 100, 255],
+        ),
+        (
+            BlendMode::Subtract,
+            [100, 100, 100, 255],
+            [50, 150, 0, 255],
+            [50, 0, 100, 255],
+        ),
+    ];
+    for (mode, bottom, top, expected) in cases {
+        assert_eq!(
+            composite_pixel(bottom, top, mode, 255),
+            expected,
+            "{mode:?}"
+        );
+    }
+}
+
+#[test]
+fn test_blend_opacity() {
+    let (blue, red) = ([0, 0, 255, 255], [255, 0, 0, 255]);
+    assert_eq!(
+        composite_pixel(blue, red, BlendMode::Normal, 128),
+        [128, 0, 127, 255]
+    );
+    assert_eq!(composite_pixel(blue, red, BlendMode::Normal, 0), blue);
+    // the blended color is mixed with the backdrop by the opacity
+    assert_eq!(
+        composite_pixel(
+            [128, 255, 255, 255],
+            [255, 128, 0, 255],
+            BlendMode::Multiply,
+            128
+        ),
+        [128, 192, 127, 255]
+    );
+
+    // over transparency every mode draws the color as is, faded by the opacity
+    for mode in [
+        BlendMode::Normal,
+        BlendMode::Multiply,
+        BlendMode::Difference,
+    ] {
+        assert_eq!(
+            composite_pixel([0, 0, 0, 0], red, mode, 51),
+            [255, 0, 0, 51]
+        );
+    }
+    // translucent colors over opaque ones
+    assert_eq!(
+        composite_pixel(blue, [255, 0, 0, 51], BlendMode::Normal, 255),
+        [51, 0, 204, 255]
+    );
+}
+
+#[test]
+fn test_layer_sections() {
+    let mabel = mabel(indoc! {"
+        palette:
+        b = #0000ff
+        r = #ff0000
+        w = #ffffff
+
+        # layer
+        name: background
+        -- pixels
+        bb
+        -- pixels
+
+        # layer
+        opacity: 128
+        -- pixels
+        r
+        -- pixels
+
+        # layer
+        name: hidden
+        visible: false
+        -- pixels
+         w
+        -- pixels
+
+        # layer
+        blend: difference
+        -- pixels
+         w
+        -- pixels
+    "})
+    .unwrap();
+
+    let layers = &mabel.frames[0].layers;
+    let names = layers.iter().map(|l| l.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["background", "Layer 2", "hidden", "Layer 4"]);
+    assert_eq!(layers[1].opacity, 128);
+    assert!(!layers[2].visible);
+    assert_eq!(layers[3].blend_mode, BlendMode::Difference);
+
+    let pixels = mabel.pixels();
+    assert_eq!(pixels[0][0].rgba(), [128, 0, 127, 255]);
+    // white over blue is yellow in difference, the hidden white isn't drawn
+    assert_eq!(pixels[0][1].rgba(), [255, 255, 0, 255]);
+}
+
+#[test]
+fn test_parse_blend_mode() {
+    assert_eq!(
+        parse_blend_mode("color-dodge").unwrap(),
+        BlendMode::ColorDodge
+    );
+    assert_eq!(
+        parse_blend_mode("colour").unwrap_err().to_string(),
+        "Unknown blend mode \"colour\""
+    );
+
+    let error = mabel(indoc! {"
+        palette:
+        r = #ff0000
+
+        # layer
+        blend: dissolve
+        -- pixels
+        r
+        -- pixels
+    "})
+    .unwrap_err();
+    assert_eq!(error.to_string(), "Unknown blend mode \"dissolve\"");
+}
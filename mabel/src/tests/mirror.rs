@@ -0,0 +1,91 @@
+use indoc::indoc;
+
+use crate::tests::{mabel, rgba};
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+const TRANS: [u8; 4] = [0, 0, 0, 0];
+
+#[test]
+fn test_mirror_horizontal() {
+    let mabel = mabel(indoc! {"
+        mirror: horizontal
+        palette:
+        r = #ff0000
+        b = #0000ff
+
+        -- pixels
+        rb
+        r
+        -- pixels
+    "})
+    .unwrap();
+
+    assert_eq!(
+        rgba(&mabel),
+        [[RED, BLUE, BLUE, RED], [RED, TRANS, TRANS, RED]]
+    );
+    assert_eq!(mabel.pixels_width(), 4);
+    assert_eq!(mabel.pixels_height(), 2);
+}
+
+#[test]
+fn test_mirror_odd_center() {
+    let mabel = mabel(indoc! {"
+        mirror: horizontal
+        mirror_center: odd
+        palette:
+        r = #ff0000
+        b = #0000ff
+
+        -- pixels
+        rb
+        -- pixels
+    "})
+    .unwrap();
+
+    assert_eq!(rgba(&mabel), [[RED, BLUE, RED]]);
+    assert_eq!(mabel.pixels_width(), 3);
+}
+
+#[test]
+fn test_mirror_both() {
+    let mabel = mabel(indoc! {"
+        mirror: both
+        mirror_center: odd
+        palette:
+        r = #ff0000
+        b = #0000ff
+
+        -- pixels
+        r
+        rb
+        -- pixels
+    "})
+    .unwrap();
+
+    assert_eq!(
+        rgba(&mabel),
+        [[RED, TRANS, RED], [RED, BLUE, RED], [RED, TRANS, RED]]
+    );
+}
+
+#[test]
+fn test_mirror_validates_expanded_size() {
+    let error = mabel(indoc! {"
+        width: 3
+        mirror: horizontal
+        palette:
+        r = #ff0000
+
+        -- pixels
+        rr
+        -- pixels
+    "})
+    .unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Horizontal pixels are more than the specified width"
+    );
+}
@@ -0,0 +1,269 @@
+use indoc::indoc;
+
+use crate::palette::{format_color, read_aco, read_gpl, read_hex, read_pal, Notation};
+use crate::{Mabel, Pixel};
+
+fn rgb(colors: &[color_art::Color]) -> Vec<[u8; 3]> {
+    colors
+        .iter()
+        .map(|c| [c.red(), c.green(), c.blue()])
+        .collect()
+}
+
+#[test]
+fn test_gpl() {
+    let colors = read_gpl(indoc! {"
+        GIMP Palette
+        Name: test
+        Columns: 2
+        # comment
+          0   0   0\tBlack
+        255 128   7
+    "})
+    .unwrap();
+
+    assert_eq!(rgb(&colors), [[0, 0, 0], [255, 128, 7]]);
+}
+
+#[test]
+fn test_gpl_malformed() {
+    let error = read_gpl("GIMP Palette\n255 0\n").unwrap_err();
+
+    assert_eq!(error.to_string(), "Invalid color on line 2");
+}
+
+#[test]
+fn test_hex() {
+    let colors = read_hex("ff0000\n#00ff00\n\n0000ff\n").unwrap();
+
+    assert_eq!(rgb(&colors), [[255, 0, 0], [0, 255, 0], [0, 0, 255]]);
+}
+
+#[test]
+fn test_pal() {
+    let colors = read_pal("JASC-PAL\r\n0100\r\n2\r\n255 0 0\r\n0 0 255\r\n").unwrap();
+
+    assert_eq!(rgb(&colors), [[255, 0, 0], [0, 0, 255]]);
+}
+
+#[test]
+fn test_pal_count_mismatch() {
+    let error = read_pal("JASC-PAL\n0100\n3\n255 0 0\n").unwrap_err();
+
+    assert_eq!(error.to_string(), "Expected 3 colors, found 1");
+}
+
+#[test]
+fn test_aco() {
+    let mut file = vec![];
+    for word in [
+        // version 1, two colors
+        1, 2, //
+        0, 65535, 0, 32896, 0, // rgb
+        8, 10000, 0, 0, 0, // grayscale
+        // version 2, two colors with names
+        2, 2, //
+        0, 65535, 0, 32896, 0, 0, 2, 0x61, 0, //
+        8, 10000, 0, 0, 0, 0, 1, 0,
+    ] {
+        file.extend(u16::to_be_bytes(word));
+    }
+
+    let colors = read_aco(&file).unwrap();
+
+    assert_eq!(rgb(&colors), [[255, 0, 128], [0, 0, 0]]);
+}
+
+#[test]
+fn test_aco_truncated() {
+    let error = read_aco(&[0, 1, 0, 1, 0]).unwrap_err();
+
+    assert_eq!(error.to_string(), "Unexpected end of file");
+}
+
+#[test]
+fn test_palette_file() {
+    let dir = std::env::temp_dir().join("mabel-test-palette-file");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("colors.hex"), "ff0000\n00ff00\n0000ff\n").unwrap();
+
+    let eno = mabel_eno::parse(indoc! {"
+        palette_file: colors.hex
+        palette:
+        r = 0
+        b = 2
+        w = #ffffff
+
+        -- pixels
+        rbw
+        -- pixels
+    "})
+    .unwrap();
+    let mabel = Mabel::from_dir(&eno, &dir).unwrap();
+
+    assert_eq!(
+        mabel.pixels()[0]
+            .iter()
+            .map(Pixel::rgba)
+            .collect::<Vec<_>>(),
+        [[255, 0, 0, 255], [0, 0, 255, 255], [255, 255, 255, 255]]
+    );
+}
+
+#[test]
+fn test_palette_file_unknown_index() {
+    let dir = std::env::temp_dir().join("mabel-test-palette-file-index");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("colors.hex"), "ff0000\n").unwrap();
+
+    let eno = mabel_eno::parse(indoc! {"
+        palette_file: colors.hex
+        palette:
+        r = 0
+        b = 4
+
+        -- pixels
+        rb
+        -- pixels
+    "})
+    .unwrap();
+    let error = Mabel::from_dir(&eno, &dir).unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Unknown palette index 4 on line 4, the palette file has 1 colors"
+    );
+    assert_eq!(error.downcast_ref::<mabel_eno::Error>().unwrap().line, 4);
+}
+
+#[test]
+fn test_palette_preset() {
+    let mabel = Mabel::from(
+        &mabel_eno::parse(indoc! {"
+            palette_preset: pico-8
+            palette:
+            f = #ffffff
+
+            -- pixels
+            08f
+            -- pixels
+        "})
+        .unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        mabel.pixels()[0]
+            .iter()
+            .map(Pixel::rgba)
+            .collect::<Vec<_>>(),
+        [[0, 0, 0, 255], [255, 0, 77, 255], [255, 255, 255, 255]]
+    );
+}
+
+#[test]
+fn test_palette_preset_keys() {
+    let db32 = crate::palette::presets::find("db32").unwrap();
+    let mabel = Mabel::from(
+        &mabel_eno::parse(indoc! {"
+            palette_preset: DB32
+
+            -- pixels
+            0v
+            -- pixels
+        "})
+        .unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(db32.colors.len(), 32);
+    assert_eq!(
+        mabel.pixels()[0]
+            .iter()
+            .map(Pixel::rgba)
+            .collect::<Vec<_>>(),
+        [[0, 0, 0, 255], [138, 111, 48, 255]]
+    );
+}
+
+#[test]
+fn test_unknown_palette_preset() {
+    let error = Mabel::from(
+        &mabel_eno::parse(indoc! {"
+            palette_preset: pico-9
+
+            -- pixels
+            0
+            -- pixels
+        "})
+        .unwrap(),
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Unknown palette preset \"pico-9\" on line 1"
+    );
+}
+
+#[test]
+fn test_format_color() {
+    let notation = |s: &str| s.parse::<Notation>().unwrap();
+
+    assert_eq!(format_color([255, 0, 0, 255], notation("hex")), "#ff0000");
+    assert_eq!(
+        format_color([255, 0, 0, 255], notation("rgb")),
+        "rgb(255, 0, 0)"
+    );
+    assert_eq!(
+        format_color([255, 0, 0, 255], notation("hsl")),
+        "hsl(0, 100%, 50%)"
+    );
+    assert_eq!(format_color([255, 0, 0, 255], notation("named")), "red");
+    assert_eq!(format_color([0, 255, 255, 255], notation("named")), "cyan");
+    assert_eq!(
+        format_color([128, 128, 128, 255], notation("named")),
+        "gray"
+    );
+    assert_eq!(
+        format_color([18, 52, 86, 255], notation("named")),
+        "#123456"
+    );
+    // hex and names can't hold alpha
+    assert_eq!(
+        format_color([255, 0, 0, 51], notation("hex")),
+        "rgba(255, 0, 0, 0.2)"
+    );
+    assert_eq!(
+        format_color([255, 0, 0, 51], notation("hsl")),
+        "hsla(0, 100%, 50%, 0.2)"
+    );
+    assert_eq!(
+        format_color([255, 0, 0, 100], notation("rgb")),
+        "rgba(255, 0, 0, 0.392)"
+    );
+    assert!("cmyk".parse::<Notation>().is_err());
+
+    // every notation reads back as the same color
+    let colors = [
+        [18, 52, 86, 255],
+        [200, 17, 3, 255],
+        [7, 7, 7, 255],
+        [250, 240, 230, 255],
+        [18, 52, 86, 1],
+        [200, 17, 3, 100],
+        [7, 7, 7, 128],
+    ];
+    let alphas = (0..=255).map(|a| [255, 0, 0, a]);
+    for notation in ["hex", "rgb", "hsl", "named"].map(notation) {
+        for rgba in colors.into_iter().chain(alphas.clone()) {
+            let value = format_color(rgba, notation);
+            let mabel = Mabel::from(
+                &mabel_eno::parse(&format!("palette:\nc = {value}\n-- pixels\nc\n-- pixels"))
+                    .unwrap(),
+            )
+            .unwrap();
+            assert_eq!(mabel.palette["c"].rgba(), rgba, "{value}");
+        }
+    }
+}
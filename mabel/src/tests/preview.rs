@@ -0,0 +1,77 @@
+use indoc::indoc;
+
+use crate::{
+    preview::{ansi256, base64, Protocol},
+    tests::mabel,
+};
+
+const DOCUMENT: &str = indoc! {"
+    palette:
+    r = #ff0000
+
+    -- pixels
+    r
+    r
+     r
+    -- pixels
+"};
+
+#[test]
+fn test_preview_truecolor() {
+    let preview = mabel(DOCUMENT)
+        .unwrap()
+        .preview(Protocol::Truecolor)
+        .unwrap();
+
+    assert_eq!(
+        preview,
+        concat!(
+            "\x1b[38;2;255;0;0m\x1b[48;2;255;0;0m▀",
+            // transparent pixels are drawn over a checkerboard
+            "\x1b[38;2;153;153;153m\x1b[48;2;204;204;204m▀\x1b[0m\n",
+            "\x1b[38;2;204;204;204m\x1b[49m▀",
+            "\x1b[38;2;255;0;0m\x1b[49m▀\x1b[0m\n",
+        )
+    );
+}
+
+#[test]
+fn test_preview_256() {
+    let preview = mabel(DOCUMENT).unwrap().preview(Protocol::Ansi256).unwrap();
+
+    assert!(preview.starts_with("\x1b[38;5;196m\x1b[48;5;196m▀"));
+}
+
+#[test]
+fn test_ansi256() {
+    assert_eq!(ansi256([255, 0, 0]), 196);
+    assert_eq!(ansi256([0, 0, 0]), 16);
+    assert_eq!(ansi256([255, 255, 255]), 231);
+    assert_eq!(ansi256([128, 128, 128]), 244);
+}
+
+#[test]
+fn test_preview_sixel() {
+    let preview = mabel(DOCUMENT).unwrap().preview(Protocol::Sixel).unwrap();
+
+    assert!(preview.starts_with("\x1bPq\"1;1;2;3#0;2;100;0;0"));
+    assert!(preview.ends_with("\x1b\\\n"));
+}
+
+#[test]
+fn test_preview_kitty() {
+    let preview = mabel(DOCUMENT).unwrap().preview(Protocol::Kitty).unwrap();
+
+    assert_eq!(
+        preview,
+        "\x1b_Ga=T,f=32,s=2,v=3,m=0;/wAA/wAAAAD/AAD/AAAAAAAAAAD/AAD/\x1b\\\n"
+    );
+}
+
+#[test]
+fn test_base64() {
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"m"), "bQ==");
+    assert_eq!(base64(b"ma"), "bWE=");
+    assert_eq!(base64(b"mabel"), "bWFiZWw=");
+}
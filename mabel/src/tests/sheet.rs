@@ -0,0 +1,183 @@
+use indoc::indoc;
+
+use crate::{
+    sheet::{Packing, Rect, SheetOptions},
+    tests::mabel,
+    Mabel,
+};
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+const TRANS: [u8; 4] = [0, 0, 0, 0];
+
+const SPRITES: &str = indoc! {"
+    palette:
+    r = #ff0000
+    b = #0000ff
+
+    # sprite
+    name: tall
+    -- pixels
+    r
+    r
+    -- pixels
+
+    # sprite
+    name: wide
+    palette:
+    r = #0000ff
+    -- pixels
+    rr
+    -- pixels
+"};
+
+fn rects(mabel: &Mabel, options: SheetOptions) -> Vec<(String, Rect)> {
+    mabel.sheet(options).unwrap().sprites
+}
+
+fn rect(x: u32, y: u32, width: u32, height: u32) -> Rect {
+    Rect {
+        x,
+        y,
+        width,
+        height,
+    }
+}
+
+#[test]
+fn test_sheet_sprites() {
+    let mabel = mabel(SPRITES).unwrap();
+
+    assert!(mabel.frames.is_empty());
+    assert_eq!(mabel.sprites.len(), 2);
+    assert_eq!(mabel.sprites[0].name, "tall");
+    assert_eq!(mabel.sprites[1].pixels()[0][0].rgba(), BLUE);
+}
+
+#[test]
+fn test_sheet_rows() {
+    let mabel = mabel(SPRITES).unwrap();
+    let sheet = mabel
+        .sheet(SheetOptions {
+            packing: Packing::Rows,
+            padding: 1,
+            ..Default::default()
+        })
+        .unwrap();
+
+    assert_eq!(sheet.image.dimensions(), (4, 2));
+    assert_eq!(
+        sheet.sprites,
+        [
+            ("tall".to_owned(), rect(0, 0, 1, 2)),
+            ("wide".to_owned(), rect(2, 0, 2, 1))
+        ]
+    );
+    assert_eq!(sheet.image.get_pixel(0, 1).0, RED);
+    assert_eq!(sheet.image.get_pixel(1, 0).0, TRANS);
+    assert_eq!(sheet.image.get_pixel(3, 0).0, BLUE);
+}
+
+#[test]
+fn test_sheet_columns() {
+    let mabel = mabel(SPRITES).unwrap();
+
+    assert_eq!(
+        rects(
+            &mabel,
+            SheetOptions {
+                packing: Packing::Columns,
+                ..Default::default()
+            }
+        ),
+        [
+            ("tall".to_owned(), rect(0, 0, 1, 2)),
+            ("wide".to_owned(), rect(0, 2, 2, 1))
+        ]
+    );
+}
+
+#[test]
+fn test_sheet_packed_extrude() {
+    let mabel = mabel(SPRITES).unwrap();
+    let sheet = mabel
+        .sheet(SheetOptions {
+            packing: Packing::Packed,
+            padding: 0,
+            extrude: true,
+        })
+        .unwrap();
+
+    // the extruded cells are 3x4 and 4x3, too wide to share a shelf
+    assert_eq!(sheet.image.dimensions(), (4, 7));
+    assert_eq!(
+        sheet.sprites,
+        [
+            ("tall".to_owned(), rect(1, 1, 1, 2)),
+            ("wide".to_owned(), rect(1, 5, 2, 1))
+        ]
+    );
+    assert_eq!(sheet.image.get_pixel(0, 0).0, RED);
+    assert_eq!(sheet.image.get_pixel(2, 3).0, RED);
+    assert_eq!(sheet.image.get_pixel(0, 4).0, BLUE);
+}
+
+#[test]
+fn test_sheet_scaled() {
+    let mabel = mabel(&format!("size: 2\n{SPRITES}")).unwrap();
+
+    assert_eq!(
+        rects(
+            &mabel,
+            SheetOptions {
+                packing: Packing::Rows,
+                ..Default::default()
+            }
+        )[1],
+        ("wide".to_owned(), rect(2, 0, 4, 2))
+    );
+}
+
+#[test]
+fn test_sheet_data() {
+    let mabel = mabel(SPRITES).unwrap();
+    let sheet = mabel
+        .sheet(SheetOptions {
+            packing: Packing::Rows,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let json = sheet.to_json("sheet.png");
+    assert!(json.contains(r#""wide": { "frame": { "x": 1, "y": 0, "w": 2, "h": 1 } }"#));
+    assert!(json.contains(r#""image": "sheet.png""#));
+
+    let eno = mabel_eno::parse(&sheet.to_eno("sheet.png")).unwrap();
+    assert_eq!(
+        eno.field("width").unwrap().required_value::<u32>().unwrap(),
+        3
+    );
+}
+
+#[test]
+fn test_sheet_duplicate_name() {
+    let err = mabel(indoc! {"
+        palette:
+        r = #ff0000
+
+        # sprite
+        name: a
+        -- pixels
+        r
+        -- pixels
+
+        # sprite
+        name: a
+        -- pixels
+        r
+        -- pixels
+    "})
+    .unwrap_err();
+
+    assert_eq!(err.to_string(), "Duplicate sprite name \"a\"");
+}
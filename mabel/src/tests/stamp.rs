@@ -0,0 +1,206 @@
+use std::path::PathBuf;
+
+use indoc::indoc;
+
+use crate::{stamp::IncludeError, tests::rgba, Mabel};
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+const TRANS: [u8; 4] = [0, 0, 0, 0];
+
+fn dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    std::fs::create_dir_all(&dir).unwrap();
+    for (file, contents) in files {
+        std::fs::write(dir.join(file), contents).unwrap();
+    }
+
+    dir
+}
+
+const EYE: &str = indoc! {"
+    palette:
+    r = #ff0000
+    b = #0000ff
+
+    -- pixels
+    rb
+    -- pixels
+"};
+
+#[test]
+fn test_stamp() {
+    let dir = dir(
+        "mabel-test-stamp",
+        &[
+            ("eye.eno", EYE),
+            (
+                "face.eno",
+                indoc! {"
+                    palette:
+                    g = #00ff00
+
+                    -- pixels
+                    gggg
+                    -- pixels
+
+                    # stamp
+                    file: eye.eno
+                    x: 1
+                    y: 1
+                "},
+            ),
+        ],
+    );
+
+    let mabel = Mabel::from_file(dir.join("face.eno").to_str().unwrap()).unwrap();
+
+    assert_eq!(
+        rgba(&mabel),
+        [
+            vec![GREEN, GREEN, GREEN, GREEN],
+            vec![TRANS, RED, BLUE, TRANS]
+        ]
+    );
+    assert_eq!(mabel.dependencies, [dir.join("eye.eno")]);
+}
+
+#[test]
+fn test_stamp_flip_and_remap() {
+    let dir = dir(
+        "mabel-test-stamp-flip",
+        &[
+            ("eye.eno", EYE),
+            (
+                "face.eno",
+                indoc! {"
+                    palette:
+                    r = #00ff00
+
+                    # stamp
+                    file: eye.eno
+                    flip: horizontal
+                "},
+            ),
+        ],
+    );
+
+    let mabel = Mabel::from_file(dir.join("face.eno").to_str().unwrap()).unwrap();
+
+    assert_eq!(rgba(&mabel), [[BLUE, GREEN]]);
+}
+
+#[test]
+fn test_stamp_remap_by_key() {
+    let dir = dir(
+        "mabel-test-stamp-keys",
+        &[
+            (
+                "eye.eno",
+                indoc! {"
+                    palette:
+                    r = #ff0000
+                    s = #ff0000
+
+                    -- pixels
+                    rsr
+                    -- pixels
+                "},
+            ),
+            (
+                "face.eno",
+                indoc! {"
+                    palette:
+                    r = #00ff00
+                    s = #0000ff
+
+                    # stamp
+                    file: eye.eno
+                "},
+            ),
+        ],
+    );
+
+    // keys sharing a color in the stamp keep their own host colors
+    let mabel = Mabel::from_file(dir.join("face.eno").to_str().unwrap()).unwrap();
+
+    assert_eq!(rgba(&mabel), [[GREEN, BLUE, GREEN]]);
+}
+
+#[test]
+fn test_stamp_cycle() {
+    let dir = dir(
+        "mabel-test-stamp-cycle",
+        &[
+            ("a.eno", "# stamp\nfile: b.eno\n"),
+            ("b.eno", "# stamp\nfile: a.eno\n"),
+        ],
+    );
+
+    let error = Mabel::from_file(dir.join("a.eno").to_str().unwrap()).unwrap_err();
+    let error = error.downcast_ref::<IncludeError>().unwrap();
+
+    assert_eq!(error.includes.len(), 2);
+    assert!(error.source.to_string().starts_with("Stamp cycle "));
+    assert!(error.source.to_string().ends_with("a.eno"));
+    // the chain is only written once
+    assert_eq!(error.to_string(), error.source.to_string());
+    assert!(!error.to_string().contains("included from"));
+}
+
+#[test]
+fn test_stamp_error_chain() {
+    let dir = dir(
+        "mabel-test-stamp-chain",
+        &[
+            ("eye.eno", "-- pixels\nx\n-- pixels\n"),
+            ("face.eno", "# stamp\nfile: eye.eno\n"),
+            ("head.eno", "# stamp\nfile: face.eno\n"),
+        ],
+    );
+
+    let error = Mabel::from_file(dir.join("head.eno").to_str().unwrap()).unwrap_err();
+    let error = error.downcast_ref::<IncludeError>().unwrap();
+
+    assert_eq!(error.source.to_string(), "Unknown palette key \"x\"");
+    assert_eq!(
+        error
+            .includes
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>(),
+        ["head.eno", "face.eno", "eye.eno"]
+    );
+}
+
+#[test]
+fn test_stamp_mirror() {
+    let dir = dir(
+        "mabel-test-stamp-mirror",
+        &[
+            ("eye.eno", EYE),
+            (
+                "face.eno",
+                indoc! {"
+                    mirror: horizontal
+                    palette:
+                    g = #00ff00
+
+                    -- pixels
+                    g
+                    -- pixels
+
+                    # stamp
+                    file: eye.eno
+                    x: 2
+                "},
+            ),
+        ],
+    );
+
+    let mabel = Mabel::from_file(dir.join("face.eno").to_str().unwrap()).unwrap();
+
+    // the stamp neither moves the axis nor gets mirrored
+    assert_eq!(rgba(&mabel), [[GREEN, GREEN, RED, BLUE]]);
+}
@@ -0,0 +1,55 @@
+use indoc::indoc;
+
+use crate::tests::mabel;
+
+const SQUARE: &str = indoc! {"
+    size: 4
+    width: 3
+    palette:
+    r = #ff0000
+    h = rgba(0, 0, 255, 0.5)
+
+    -- pixels
+    rr
+    rrh
+    -- pixels
+"};
+
+#[test]
+fn test_svg_merged_rects() {
+    let svg = mabel(SQUARE).unwrap().to_svg(false);
+
+    assert_eq!(
+        svg,
+        indoc! {r##"
+            <svg xmlns="http://www.w3.org/2000/svg" width="12" height="8" viewBox="0 0 3 2" shape-rendering="crispEdges">
+            <rect x="0" y="0" width="2" height="2" fill="#ff0000"/>
+            <rect x="2" y="1" width="1" height="1" fill="#0000ff" fill-opacity="0.502"/>
+            </svg>
+        "##}
+    );
+}
+
+#[test]
+fn test_svg_classes() {
+    let svg = mabel(SQUARE).unwrap().to_svg(true);
+
+    assert!(svg.contains("<g class=\"key-r\" fill=\"#ff0000\">\n<rect x=\"0\" y=\"0\" width=\"2\" height=\"2\"/>\n</g>"));
+    assert!(svg.contains("<g class=\"key-h\" fill=\"#0000ff\" fill-opacity=\"0.502\">"));
+}
+
+#[test]
+fn test_svg_class_names() {
+    let svg = mabel(indoc! {"
+        palette:
+        🌸 = #ffc0cb
+
+        -- pixels
+        🌸
+        -- pixels
+    "})
+    .unwrap()
+    .to_svg(true);
+
+    assert!(svg.contains("class=\"key-u1f338\""));
+}
@@ -0,0 +1,171 @@
+use indoc::indoc;
+
+use crate::tests::{mabel, rgba};
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+const TRANS: [u8; 4] = [0, 0, 0, 0];
+
+#[test]
+fn test_block_element_keys() {
+    let mabel = mabel(indoc! {"
+        palette:
+        █ = #ff0000
+        ░ = #0000ff
+
+        -- pixels
+        █░█
+        ░ ░
+        -- pixels
+    "})
+    .unwrap();
+
+    assert_eq!(rgba(&mabel), [[RED, BLUE, RED], [BLUE, TRANS, BLUE]]);
+}
+
+#[test]
+fn test_combining_mark_keys() {
+    let mabel = mabel(indoc! {"
+        palette:
+        e\u{301} = #ff0000
+        e = #0000ff
+
+        -- pixels
+        e\u{301}ee\u{301}
+        -- pixels
+    "})
+    .unwrap();
+
+    assert_eq!(rgba(&mabel), [[RED, BLUE, RED]]);
+    assert_eq!(mabel.pixels_width(), 3);
+}
+
+#[test]
+fn test_combining_mark_multi_character_keys() {
+    let mabel = mabel(indoc! {"
+        key_width: 2
+        palette:
+        a\u{308}1 = #ff0000
+
+        -- pixels
+        a\u{308}1  a\u{308}1
+        -- pixels
+    "})
+    .unwrap();
+
+    assert_eq!(rgba(&mabel), [[RED, TRANS, RED]]);
+}
+
+#[test]
+fn test_multiple_graphemes_key() {
+    let error = mabel(indoc! {"
+        palette:
+        ab = #ff0000
+
+        -- pixels
+        ab
+        -- pixels
+    "})
+    .unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Invalid palette key \"ab\", must be a single character"
+    );
+}
+
+#[test]
+fn test_emoji_keys() {
+    let mabel = mabel(indoc! {"
+        palette:
+        🍁 = #ff0000
+        👩‍🚀 = #0000ff
+
+        -- pixels
+        🍁  👩‍🚀
+        👩‍🚀🍁
+        -- pixels
+    "})
+    .unwrap();
+
+    assert_eq!(rgba(&mabel), [vec![RED, TRANS, BLUE], vec![BLUE, RED]]);
+}
+
+#[test]
+fn test_wide_keys_keep_narrow_keys_aligned() {
+    let mabel = mabel(indoc! {"
+        palette:
+        あ = #ff0000
+        b = #0000ff
+
+        -- pixels
+        b あb
+        あb b
+        -- pixels
+    "})
+    .unwrap();
+
+    assert_eq!(rgba(&mabel), [[BLUE, RED, BLUE], [RED, BLUE, BLUE]]);
+    assert_eq!(mabel.pixels_width(), 3);
+}
+
+#[test]
+fn test_unknown_key() {
+    let error = mabel(indoc! {"
+        palette:
+        █ = #ff0000
+
+        -- pixels
+        █▓
+        -- pixels
+    "})
+    .unwrap_err();
+
+    assert_eq!(error.to_string(), "Unknown palette key \"▓\"");
+}
+
+#[test]
+fn test_wide_keys_transparent_cells() {
+    let wide = mabel(indoc! {"
+        palette:
+        宽 = #00ff00
+        a = #ff0000
+        b = #0000ff
+
+        -- pixels
+        a   b
+        a b
+        宽  a
+          宽
+        -- pixels
+    "})
+    .unwrap();
+
+    // narrow keys always take a space of padding, transparent cells included
+    let green = [0, 255, 0, 255];
+    assert_eq!(
+        rgba(&wide),
+        [
+            vec![RED, TRANS, BLUE],
+            vec![RED, BLUE],
+            vec![green, TRANS, RED],
+            vec![TRANS, green]
+        ]
+    );
+
+    let error = mabel(indoc! {"
+        palette:
+        宽 = #00ff00
+        a = #ff0000
+
+        -- pixels
+        a 宽
+        a宽
+        -- pixels
+    "})
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Pixels line 2 needs padding after \"a\" to line up with the widest palette key"
+    );
+}
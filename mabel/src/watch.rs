@@ -0,0 +1,193 @@
+// mabel, declarative pixel art
+// Copyright (c) 2024 fawn
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use mabel::{format::Format, stamp::IncludeError, Mabel, Result};
+
+use crate::{print_error, render, MabelArgs};
+
+/// How often watched files are checked for changes.
+pub const INTERVAL: Duration = Duration::from_millis(250);
+
+/// A document along with where it's rendered to.
+struct Job {
+    file: PathBuf,
+    output: String,
+    format: Format,
+    data: Option<String>,
+    /// The document and the files it depends on, with the modification times
+    /// they had when it was last rendered.
+    files: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl Job {
+    fn new(file: PathBuf, output: String, format: Format, data: Option<String>) -> Self {
+        Self {
+            file,
+            output,
+            format,
+            data,
+            files: HashMap::new(),
+        }
+    }
+
+    fn is_changed(&self) -> bool {
+        self.files.is_empty() || self.files.iter().any(|(f, time)| modified(f) != *time)
+    }
+
+    fn render(&mut self, args: &MabelArgs) {
+        let mut files = vec![self.file.clone()];
+        let result = Mabel::from_file(&self.file.to_string_lossy()).and_then(|mabel| {
+            render(
+                &mabel,
+                &self.output,
+                self.format,
+                self.data.as_deref(),
+                args,
+            )?;
+            Ok(mabel)
+        });
+
+        match result {
+            Ok(mabel) => {
+                files.extend(mabel.dependencies);
+                println!(
+                    "\x1b[1;32mrendered\x1b[0m {} -> {}",
+                    self.file.display(),
+                    self.output
+                );
+            }
+            Err(e) => {
+                // the dependencies are unknown until the document reads again,
+                // so keep the previous ones along with any failed stamps
+                files.extend(self.files.keys().cloned());
+                if let Some(e) = e.downcast_ref::<IncludeError>() {
+                    files.extend(e.includes.iter().cloned());
+                }
+                print_error(&e);
+            }
+        }
+
+        self.files = files
+            .into_iter()
+            .map(|f| {
+                let time = modified(&f);
+                (f, time)
+            })
+            .collect();
+    }
+}
+
+/// Renders `path` whenever it or its dependencies change, until interrupted.
+///
+/// When `path` is a directory every `.eno` file in it is rendered into the
+/// output directory, which defaults to `path` itself, as `<name>.<format>`.
+pub fn watch(path: &str, format: Option<Format>, args: &MabelArgs) -> Result<()> {
+    let dir = Path::new(path).is_dir().then(|| {
+        let output = args.output.as_deref().unwrap_or(path);
+        (Path::new(path), Path::new(output))
+    });
+
+    let mut jobs = vec![];
+    match dir {
+        Some((_, output)) => std::fs::create_dir_all(output)?,
+        None => {
+            let output = args
+                .output
+                .clone()
+                .unwrap_or_else(|| format!("output.{}", format.unwrap_or(Format::Png).extension()));
+            let format = match format {
+                Some(format) => format,
+                None => Format::from_path(&output)?,
+            };
+            jobs.push(Job::new(path.into(), output, format, args.data.clone()));
+        }
+    }
+
+    println!("watching {path}, press ctrl-c to stop");
+    loop {
+        if let Some((dir, output)) = dir {
+            let documents = documents(dir)?;
+            jobs.retain(|j| documents.contains(&j.file));
+            for file in documents {
+                if jobs.iter().any(|j| j.file == file) {
+                    continue;
+                }
+
+                let format = format.unwrap_or(Format::Png);
+                let name = file.file_stem().unwrap_or_default().to_string_lossy();
+                let output = output.join(format!("{name}.{}", format.extension()));
+                jobs.push(Job::new(
+                    file,
+                    output.to_string_lossy().into_owned(),
+                    format,
+                    None,
+                ));
+            }
+        }
+
+        for job in &mut jobs {
+            if job.is_changed() {
+                job.render(args);
+            }
+        }
+
+        std::thread::sleep(INTERVAL);
+    }
+}
+
+/// The `.eno` files in `dir`, sorted by name.
+pub fn documents(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut documents = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().is_some_and(|e| e == "eno"))
+        .collect::<Vec<_>>();
+    documents.sort();
+
+    Ok(documents)
+}
+
+/// When `path` was last modified, `None` when it doesn't exist.
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_documents() {
+        let dir = std::env::temp_dir().join("mabel-watch-documents");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested.eno")).unwrap();
+        for file in ["b.eno", "a.eno", "c.png", "eno"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+
+        // only .eno files, not directories, sorted by name
+        assert_eq!(
+            documents(&dir).unwrap(),
+            [dir.join("a.eno"), dir.join("b.eno")]
+        );
+        assert!(documents(&dir.join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_modified() {
+        let dir = std::env::temp_dir().join("mabel-watch-modified");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.eno");
+        std::fs::write(&file, "").unwrap();
+
+        assert!(modified(&file).is_some());
+        assert_eq!(modified(&dir.join("missing.eno")), None);
+    }
+}